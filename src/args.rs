@@ -0,0 +1,3004 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::CString,
+    fmt,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use crate::pattern::Pattern;
+
+use windows::Win32::Graphics::{
+    Direct3D::Fxc::{
+        D3DCOMPILER_STRIP_DEBUG_INFO, D3DCOMPILER_STRIP_PRIVATE_DATA,
+        D3DCOMPILER_STRIP_REFLECTION_DATA, D3DCOMPILE_ALL_RESOURCES_BOUND,
+        D3DCOMPILE_AVOID_FLOW_CONTROL, D3DCOMPILE_DEBUG, D3DCOMPILE_ENABLE_BACKWARDS_COMPATIBILITY,
+        D3DCOMPILE_ENABLE_STRICTNESS, D3DCOMPILE_ENABLE_UNBOUNDED_DESCRIPTOR_TABLES,
+        D3DCOMPILE_IEEE_STRICTNESS, D3DCOMPILE_NO_PRESHADER, D3DCOMPILE_OPTIMIZATION_LEVEL0,
+        D3DCOMPILE_OPTIMIZATION_LEVEL1, D3DCOMPILE_OPTIMIZATION_LEVEL3,
+        D3DCOMPILE_PACK_MATRIX_COLUMN_MAJOR, D3DCOMPILE_PACK_MATRIX_ROW_MAJOR,
+        D3DCOMPILE_PARTIAL_PRECISION, D3DCOMPILE_RESOURCES_MAY_ALIAS,
+        D3DCOMPILE_SECDATA_MERGE_UAV_SLOTS, D3DCOMPILE_SECDATA_PRESERVE_TEMPLATE_SLOTS,
+        D3DCOMPILE_SKIP_OPTIMIZATION, D3DCOMPILE_SKIP_VALIDATION, D3DCOMPILE_WARNINGS_ARE_ERRORS,
+    },
+    Hlsl::D3DCOMPILE_OPTIMIZATION_LEVEL2,
+};
+
+/// Every /T profile fxc knows about, grouped by stage. Which of them can actually be compiled
+/// depends on the version of the compiler DLL.
+pub static PROFILES: &[&str] = &[
+    "vs_1_1",
+    "vs_2_0",
+    "vs_2_a",
+    "vs_2_sw",
+    "vs_3_0",
+    "vs_3_sw",
+    "vs_4_0_level_9_1",
+    "vs_4_0_level_9_3",
+    "vs_4_0",
+    "vs_4_1",
+    "vs_5_0",
+    "vs_5_1",
+    "ps_2_0",
+    "ps_2_a",
+    "ps_2_b",
+    "ps_2_sw",
+    "ps_3_0",
+    "ps_3_sw",
+    "ps_4_0_level_9_1",
+    "ps_4_0_level_9_3",
+    "ps_4_0",
+    "ps_4_1",
+    "ps_5_0",
+    "ps_5_1",
+    "gs_4_0",
+    "gs_4_1",
+    "gs_5_0",
+    "gs_5_1",
+    "hs_5_0",
+    "hs_5_1",
+    "ds_5_0",
+    "ds_5_1",
+    "cs_4_0",
+    "cs_4_1",
+    "cs_5_0",
+    "cs_5_1",
+    "lib_4_0",
+    "lib_4_1",
+    "lib_5_0",
+    "fx_2_0",
+    "fx_4_0",
+    "fx_4_1",
+    "fx_5_0",
+    "rootsig_1_0",
+    "rootsig_1_1",
+];
+
+/// C and C++ keywords, which can't be used as the /Vn variable name in the generated header.
+static C_KEYWORDS: &[&str] = &[
+    "alignas",
+    "alignof",
+    "and",
+    "and_eq",
+    "asm",
+    "auto",
+    "bitand",
+    "bitor",
+    "bool",
+    "break",
+    "case",
+    "catch",
+    "char",
+    "char8_t",
+    "char16_t",
+    "char32_t",
+    "class",
+    "compl",
+    "concept",
+    "const",
+    "consteval",
+    "constexpr",
+    "constinit",
+    "const_cast",
+    "continue",
+    "co_await",
+    "co_return",
+    "co_yield",
+    "decltype",
+    "default",
+    "delete",
+    "do",
+    "double",
+    "dynamic_cast",
+    "else",
+    "enum",
+    "explicit",
+    "export",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "friend",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "mutable",
+    "namespace",
+    "new",
+    "noexcept",
+    "not",
+    "not_eq",
+    "nullptr",
+    "operator",
+    "or",
+    "or_eq",
+    "private",
+    "protected",
+    "public",
+    "register",
+    "reinterpret_cast",
+    "requires",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "static_cast",
+    "struct",
+    "switch",
+    "template",
+    "this",
+    "thread_local",
+    "throw",
+    "true",
+    "try",
+    "typedef",
+    "typeid",
+    "typename",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "wchar_t",
+    "while",
+    "xor",
+    "xor_eq",
+];
+
+struct ProfilePrefix {
+    name: &'static str,
+    prefix: &'static str,
+}
+
+static PROFILE_PREFIX_TABLE: [ProfilePrefix; 12] = [
+    ProfilePrefix {
+        name: "ps_2_0",
+        prefix: "g_ps20",
+    },
+    ProfilePrefix {
+        name: "ps_2_a",
+        prefix: "g_ps21",
+    },
+    ProfilePrefix {
+        name: "ps_2_b",
+        prefix: "g_ps21",
+    },
+    ProfilePrefix {
+        name: "ps_2_sw",
+        prefix: "g_ps2ff",
+    },
+    ProfilePrefix {
+        name: "ps_3_0",
+        prefix: "g_ps30",
+    },
+    ProfilePrefix {
+        name: "ps_3_sw",
+        prefix: "g_ps3ff",
+    },
+    ProfilePrefix {
+        name: "vs_1_1",
+        prefix: "g_vs11",
+    },
+    ProfilePrefix {
+        name: "vs_2_0",
+        prefix: "g_vs20",
+    },
+    ProfilePrefix {
+        name: "vs_2_a",
+        prefix: "g_vs21",
+    },
+    ProfilePrefix {
+        name: "vs_2_sw",
+        prefix: "g_vs2ff",
+    },
+    ProfilePrefix {
+        name: "vs_3_0",
+        prefix: "g_vs30",
+    },
+    ProfilePrefix {
+        name: "vs_3_sw",
+        prefix: "g_vs3ff",
+    },
+];
+
+/// The extensions --strict-args accepts for input files: shader sources, and compiled objects for
+/// /dumpbin.
+static INPUT_EXTENSIONS: [&str; 9] = [
+    "hlsl", "hlsli", "fx", "fxh", "vsh", "psh", "cso", "fxo", "dxbc",
+];
+
+/// The widest --indent and --byte-width taken, in spaces.
+const MAX_LAYOUT_WIDTH: usize = 16;
+
+/// The most files --split-header splits the /Fh array into.
+const MAX_HEADER_PARTS: usize = 256;
+
+pub enum UsageError {
+    HelpRequested,
+    InvalidArgument(String),
+    UnknownArgument(String),
+    MissingArgument(String),
+    TooManyArguments,
+    ConflictingArguments(String, String),
+    DuplicateDefine {
+        name: String,
+        first: String,
+        second: String,
+    },
+    InvalidCompileDb(String, String),
+    InvalidResponseFile(String, String),
+    InvalidPragma(String, String),
+    NotInCompileDb(String, String),
+    EntryPointMismatch {
+        entry_point: String,
+        pattern: String,
+    },
+    SameOutputPath {
+        first: String,
+        second: String,
+        path: String,
+    },
+    UnexpectedInput(String),
+}
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UsageError::HelpRequested => write!(f, "Check https://learn.microsoft.com/en-us/windows/win32/direct3dtools/dx-graphics-tools-fxc-syntax for usage information."),
+            UsageError::InvalidArgument(arg) => write!(f, "Invalid argument: '{arg}'"),
+            UsageError::UnknownArgument(arg) => {
+                writeln!(f, "Unknown argument: '{arg}'")?;
+                writeln!(f, "This isn't a sign of disaster, odds are it will be very easy to add support for this argument.")?;
+                writeln!(f, "Review the meaning of the argument in the real fxc program, and then add it into fxc2.")
+            }
+            UsageError::MissingArgument(arg) => {
+                writeln!(f, "Missing argument for: '{arg}'")?;
+                writeln!(f, "We expected to receive this, and it's likely things will nmot work correctly without it.")?;
+                writeln!(f, "Review fxc2 and make sure things will work.")
+            }
+            UsageError::TooManyArguments => write!(f, "You specified multiple input files with --compile-db, which looks up the arguments of a single file. Compile them one at a time."),
+            UsageError::ConflictingArguments(first, second) => write!(f, "Options '{first}' and '{second}' can't be used together."),
+            UsageError::DuplicateDefine { name, first, second } => write!(f, "Macro '{name}' is defined more than once, as '{first}' and as '{second}'."),
+            UsageError::InvalidCompileDb(db, err) => write!(f, "Can't read the compile database '{db}': {err}"),
+            UsageError::InvalidResponseFile(file, err) => write!(f, "Can't read the response file '{file}': {err}"),
+            UsageError::InvalidPragma(file, err) => write!(f, "Invalid `#pragma fxc_flags` in '{file}': {err}"),
+            UsageError::NotInCompileDb(db, file) => write!(f, "'{file}' has no entry in the compile database '{db}'."),
+            UsageError::EntryPointMismatch { entry_point, pattern } => write!(f, "Entry point '{entry_point}' doesn't match the required pattern '{pattern}'."),
+            UsageError::SameOutputPath { first, second, path } => write!(f, "Options '{first}' and '{second}' both write to '{path}', one would overwrite the other."),
+            UsageError::UnexpectedInput(arg) => write!(f, "'{arg}' doesn't look like an input file. --strict-args only takes inputs ending in {}; if it's an option, it's missing its leading '/' or '-', if it's the input file, give it after '--'.", INPUT_EXTENSIONS.map(|extension| format!(".{extension}")).join(", ")),
+        }
+    }
+}
+
+impl From<UsageError> for ExitCode {
+    fn from(err: UsageError) -> ExitCode {
+        eprintln!("{err}");
+        ExitCode::FAILURE
+    }
+}
+
+enum Opts {
+    /// (T), Required
+    Model(String),
+    /// (?, help), Optional
+    Help,
+    /// (all_resources_bound), Optional
+    AllResourcesBound,
+    /// (D), Optional
+    Define(CString, CString),
+    /// (dumpbin), Optional
+    DumpBin,
+    /// (compress), Optional
+    Compress,
+    /// (decompress), Optional
+    Decompress,
+    /// (E), Required
+    EntryPointName(CString),
+    /// (enable_unbounded_descriptor_tables), Optional
+    UnboundedDescriptorTables,
+    /// (Fh), Required
+    OutputFile(String),
+    /// (Fo), Optional
+    ObjectFile(String),
+    /// (Fc), Optional
+    AssemblyFile(String),
+    /// (setprivate), Optional
+    SetPrivate(String),
+    /// (getprivate), Optional
+    GetPrivate(String),
+    /// (setrootsignature), Optional
+    SetRootSignature(String),
+    /// (extractrootsignature), Optional
+    ExtractRootSignature(String),
+    /// (verifyrootsignature), Optional
+    VerifyRootSignature(String),
+    /// (Gec), Optional
+    BackwardsCompatibility,
+    /// (Ges), Optional
+    EnableStrictness,
+    /// (Gfa), Optional
+    AvoidFlowControl,
+    /// (Gis), Optional
+    EnableIEEEStrictness,
+    /// (Gpp), Optional
+    PartialPrecision,
+    /// (matchUAVs), Optional, requires secondary data
+    MatchUAVs,
+    /// (mergeUAVs), Optional, requires secondary data
+    MergeUAVs,
+
+    // Don't know how to handle includes yet
+    /// (nologo), Optional
+    NoLogo,
+    /// (P, Fi), Optional
+    Preprocess(String),
+    /// (I), Optional
+    IncludeDir(String),
+    /// (Od), Optional
+    DisableOptimizations,
+    /// (Op), Optional
+    DisablePreshaders,
+    /// (O0), Optional
+    OptimizationLevel0,
+    /// (O1), Optional
+    OptimizationLevel1,
+    /// (O2), Optional
+    OptimizationLevel2,
+    /// (O3), Optional
+    OptimizationLevel3,
+    /// (Qstrip_rootsignature), Optional
+    StripRootSignature,
+    /// (Qstrip_reflect), Optional
+    StripReflect,
+    /// (Qstrip_debug), Optional
+    StripDebug,
+    /// (Qstrip_priv), Optional
+    StripPrivate,
+    /// (res_may_alias), Optional
+    ResourceMayAlias,
+    /// (Vd), Optional
+    SkipValidation,
+    /// (Vi), Optional
+    OutputIncludeProcessDetails,
+    /// (Vn), Optional
+    VariableName(String),
+    /// (WX), Optional
+    WarningsAsErrors,
+    /// (Zi), Optional
+    DebugInformation,
+    /// (Zpc), Optional
+    PackMatrixColumnMajor,
+    /// (Zpr)), Optional
+    PackMatrixRowMajor,
+    /// (), Input file
+    InputFile(String),
+
+    // fxc2-only options
+    /// (-echo-fxc), Optional
+    EchoFxc,
+    /// (-emit-signature), Optional
+    EmitSignature(String),
+    /// (-with-output-signature), Optional
+    WithOutputSignature,
+    /// (-minimal), Optional
+    Minimal,
+    /// (-include-ext), Optional
+    IncludeExtensions(Vec<String>),
+    /// (-reflect-json), Optional
+    ReflectJson(String),
+    /// (-strict-defines), Optional
+    StrictDefines,
+    /// (-expect-binding), Optional
+    ExpectBinding(ExpectedBinding),
+    /// (-hexdump), Optional
+    Hexdump,
+    /// (-hexdump-length), Optional
+    HexdumpLength(usize),
+    /// (-emit-loader), Optional
+    EmitLoader,
+    /// (-secondary), Optional
+    SecondaryData(String),
+    /// (-why-failed), Optional
+    WhyFailed,
+    /// (-strip-line-directives), Optional
+    StripLineDirectives,
+    /// (-expect-profile), Optional
+    ExpectProfile(String),
+    /// (-compact), Optional
+    Compact,
+    /// (-compile-db), Optional
+    CompileDb(String),
+    /// (-lang), Optional
+    Language(OutputLanguage),
+    /// (-split-header), Optional
+    SplitHeader(usize),
+    /// (-cbuffer-budget), Optional
+    CbufferBudget(u32),
+    /// (-cbuffer-budget-error), Optional
+    CbufferBudgetError,
+    /// (-sort-defines), Optional
+    SortDefines,
+    /// (-emit-descriptor-map), Optional
+    EmitDescriptorMap(String),
+    /// (-no-source-relative-includes), Optional
+    NoSourceRelativeIncludes,
+    /// (-clipboard), Optional
+    Clipboard,
+    /// (-entry-pattern), Optional
+    EntryPattern(Pattern),
+    /// (-summary), Optional
+    Summary,
+    /// (-forbid), Optional
+    Forbid(String),
+    /// (-meta), Optional
+    Meta(String),
+    /// (-meta-timestamp), Optional
+    MetaTimestamp,
+    /// (-show-hash), Optional
+    ShowHash,
+    /// (-compare-precision), Optional
+    ComparePrecision(String),
+    /// (-strict), Optional
+    Strict,
+    /// (MF, -depfile), Optional
+    DepFile(String),
+    /// (-dep-relative-to), Optional
+    DepRelativeTo(String),
+    /// (-emit-rc), Optional
+    EmitRc(String),
+    /// (-list-profiles), Optional
+    ListProfiles,
+    /// (-note), Optional
+    Note(String),
+    /// (-show-note), Optional
+    ShowNote,
+    /// (-threadgroup), Optional
+    Threadgroup,
+    /// (-byte-width), Optional
+    ByteWidth(usize),
+    /// (-indent), Optional
+    Indent(String),
+    /// (-check), Optional
+    Check,
+    /// (-max-bytes), Optional
+    MaxBytes(usize),
+    /// (-infer-from-output), Optional
+    InferFromOutput,
+    /// (-trace), Optional
+    Trace,
+    /// (-warnings-to), Optional
+    WarningsTo(WarningStream),
+    /// (-model-alias), Optional
+    ModelAlias(String, String),
+    /// (-dump-sections), Optional
+    DumpSections,
+    /// (-length-prefixed), Optional
+    LengthPrefixed,
+    /// (-emit-pso-desc), Optional
+    EmitPsoDesc(String),
+    /// (-normalize-line-endings), Optional
+    NormalizeLineEndings,
+    /// (-max-instructions), Optional
+    MaxInstructions(u32),
+    /// (-techniques), Optional
+    Techniques(String),
+    /// (-strict-args), Optional
+    StrictArgs,
+    /// (-emit-bindings-hlsl), Optional
+    EmitBindingsHlsl(String),
+    /// (-max-warnings), Optional
+    MaxWarnings(usize),
+    /// (-porcelain), Optional
+    Porcelain,
+    /// (-errors-blob), Optional
+    ErrorsBlob(String),
+    /// (-files-from), Optional
+    FilesFrom(String),
+    /// (-null), Optional
+    Null,
+    /// (-profile-timings), Optional
+    ProfileTimings,
+    /// (-embed-disasm), Optional
+    EmbedDisassembly,
+    /// (-default-prefix), Optional
+    DefaultPrefix(String),
+    /// (-write-if-changed), Optional
+    WriteIfChanged,
+    /// (-manifest), Optional
+    Manifest(String),
+    /// (-keep-temps), Optional
+    KeepTemps(String),
+    /// (-emit-enum), Optional
+    EmitEnum(String),
+    /// (-split-debug), Optional
+    SplitDebug,
+    /// (-golden), Optional
+    Golden(String),
+    /// (-update-golden), Optional
+    UpdateGolden,
+    /// (-show-defines), Optional
+    ShowDefines,
+    /// (-emit-asm-inc), Optional
+    EmitAsmInc(String),
+    /// (-asm-flavor), Optional
+    AsmFlavor(AsmFlavor),
+    /// (-timeout), Optional
+    Timeout(u64),
+}
+
+impl Opts {
+    /// Parses the first argument. If the argument requires an argument, and it is not already attached to the first, the next argument is used.
+    /// Returns true if the second argument was used.
+    fn parse(first: &str, second: Option<&str>) -> Result<(Opts, bool), UsageError> {
+        match first.chars().next() {
+            None => return Err(UsageError::InvalidArgument(first.to_owned())),
+            Some('-' | '/') if first.len() > 1 => {}
+            Some(_) => {
+                // not an option, assume it's the input file
+                return Ok((Opts::InputFile(first.to_owned()), false));
+            }
+        }
+        // trim the '-' or '/'
+        let mut first = &first[1..];
+        // handle no-arg options
+        match first {
+            "?" | "help" => return Ok((Opts::Help, false)),
+            "all_resources_bound" => return Ok((Opts::AllResourcesBound, false)),
+            "enable_unbounded_descriptor_tables" => {
+                return Ok((Opts::UnboundedDescriptorTables, false))
+            }
+            "dumpbin" => return Ok((Opts::DumpBin, false)),
+            "compress" => return Ok((Opts::Compress, false)),
+            "decompress" => return Ok((Opts::Decompress, false)),
+            "Gec" => return Ok((Opts::BackwardsCompatibility, false)),
+            "Ges" => return Ok((Opts::EnableStrictness, false)),
+            "Gfa" => return Ok((Opts::AvoidFlowControl, false)),
+            "Gis" => return Ok((Opts::EnableIEEEStrictness, false)),
+            "Gpp" => return Ok((Opts::PartialPrecision, false)),
+            "matchUAVs" => return Ok((Opts::MatchUAVs, false)),
+            "mergeUAVs" => return Ok((Opts::MergeUAVs, false)),
+            "nologo" => return Ok((Opts::NoLogo, false)),
+            "Od" => return Ok((Opts::DisableOptimizations, false)),
+            "Op" => return Ok((Opts::DisablePreshaders, false)),
+            "O0" => return Ok((Opts::OptimizationLevel0, false)),
+            "O1" => return Ok((Opts::OptimizationLevel1, false)),
+            "O2" => return Ok((Opts::OptimizationLevel2, false)),
+            "O3" => return Ok((Opts::OptimizationLevel3, false)),
+            "Qstrip_rootsignature" => return Ok((Opts::StripRootSignature, false)),
+            "Qstrip_reflect" => return Ok((Opts::StripReflect, false)),
+            "Qstrip_debug" => return Ok((Opts::StripDebug, false)),
+            "Qstrip_priv" => return Ok((Opts::StripPrivate, false)),
+            "res_may_alias" => return Ok((Opts::ResourceMayAlias, false)),
+            "Vd" => return Ok((Opts::SkipValidation, false)),
+            "Vi" => return Ok((Opts::OutputIncludeProcessDetails, false)),
+            "WX" => return Ok((Opts::WarningsAsErrors, false)),
+            "Zi" => return Ok((Opts::DebugInformation, false)),
+            "Zpc" => return Ok((Opts::PackMatrixColumnMajor, false)),
+            "Zpr" => return Ok((Opts::PackMatrixRowMajor, false)),
+            "-echo-fxc" => return Ok((Opts::EchoFxc, false)),
+            "-with-output-signature" => return Ok((Opts::WithOutputSignature, false)),
+            "-minimal" => return Ok((Opts::Minimal, false)),
+            "-strict-defines" => return Ok((Opts::StrictDefines, false)),
+            "-hexdump" => return Ok((Opts::Hexdump, false)),
+            "-emit-loader" => return Ok((Opts::EmitLoader, false)),
+            "-why-failed" => return Ok((Opts::WhyFailed, false)),
+            "-strip-line-directives" => return Ok((Opts::StripLineDirectives, false)),
+            "-compact" => return Ok((Opts::Compact, false)),
+            "-cbuffer-budget-error" => return Ok((Opts::CbufferBudgetError, false)),
+            "-sort-defines" => return Ok((Opts::SortDefines, false)),
+            "-clipboard" => return Ok((Opts::Clipboard, false)),
+            "-summary" => return Ok((Opts::Summary, false)),
+            "-meta-timestamp" => return Ok((Opts::MetaTimestamp, false)),
+            "-show-hash" => return Ok((Opts::ShowHash, false)),
+            "-strict" => return Ok((Opts::Strict, false)),
+            "-list-profiles" => return Ok((Opts::ListProfiles, false)),
+            "-show-note" => return Ok((Opts::ShowNote, false)),
+            "-threadgroup" => return Ok((Opts::Threadgroup, false)),
+            "-check" => return Ok((Opts::Check, false)),
+            "-infer-from-output" => return Ok((Opts::InferFromOutput, false)),
+            "-emit-std-array" => return Ok((Opts::Language(OutputLanguage::StdArray), false)),
+            "-trace" => return Ok((Opts::Trace, false)),
+            "-dump-sections" => return Ok((Opts::DumpSections, false)),
+            "-length-prefixed" => return Ok((Opts::LengthPrefixed, false)),
+            "-normalize-line-endings" => return Ok((Opts::NormalizeLineEndings, false)),
+            "-strict-args" => return Ok((Opts::StrictArgs, false)),
+            "-porcelain" => return Ok((Opts::Porcelain, false)),
+            "-null" => return Ok((Opts::Null, false)),
+            "-profile-timings" => return Ok((Opts::ProfileTimings, false)),
+            "-embed-disasm" => return Ok((Opts::EmbedDisassembly, false)),
+            "-write-if-changed" => return Ok((Opts::WriteIfChanged, false)),
+            "-split-debug" => return Ok((Opts::SplitDebug, false)),
+            "-update-golden" => return Ok((Opts::UpdateGolden, false)),
+            "-show-defines" => return Ok((Opts::ShowDefines, false)),
+            "-no-source-relative-includes" => return Ok((Opts::NoSourceRelativeIncludes, false)),
+            _ => {}
+        }
+        // handle options with arguments.
+        // First check if the argument is attached to the option
+        let mut argument: String = String::new();
+        let mut used_second = false;
+        const ARG_PREFIX: &[&str] = &[
+            "T",
+            "D",
+            "E",
+            "Fh",
+            "Fo",
+            "Fi",
+            "Fc",
+            "setprivate",
+            "getprivate",
+            "setrootsignature",
+            "extractrootsignature",
+            "verifyrootsignature",
+            "Vn",
+            "P",
+            "I",
+            "-emit-signature",
+            "-include-ext",
+            "-reflect-json",
+            "-expect-binding",
+            "-hexdump-length",
+            "-secondary",
+            "-expect-profile",
+            "-compile-db",
+            "-lang",
+            "-split-header",
+            "-cbuffer-budget",
+            "-emit-descriptor-map",
+            "-entry-pattern",
+            "-forbid",
+            "-meta",
+            "-compare-precision",
+            "MF",
+            "-depfile",
+            "-dep-relative-to",
+            "-emit-rc",
+            "-note",
+            "-byte-width",
+            "-indent",
+            "-max-bytes",
+            "-warnings-to",
+            "-model-alias",
+            "-emit-pso-desc",
+            "-max-instructions",
+            "-techniques",
+            "-emit-bindings-hlsl",
+            "-max-warnings",
+            "-errors-blob",
+            "-files-from",
+            "-default-prefix",
+            "-manifest",
+            "-keep-temps",
+            "-emit-enum",
+            "-golden",
+            "-emit-asm-inc",
+            "-asm-flavor",
+            "-timeout",
+        ];
+        for prefix in ARG_PREFIX.iter() {
+            let Some(arg) = first.strip_prefix(prefix) else {
+                continue;
+            };
+            // only fxc's own options take an attached value (`/Fhfoo.h`), a --long option is
+            // spelled out in full and takes its value as `--opt value` or `--opt=value`, so
+            // `--meta-timestamps` isn't `--meta -timestamps`
+            let (arg, attached) = match arg.strip_prefix('=') {
+                Some(value) if prefix.starts_with('-') => (value, true),
+                _ if prefix.starts_with('-') && !arg.is_empty() => continue,
+                _ => (arg, !arg.is_empty()),
+            };
+            first = prefix;
+            if attached {
+                argument = arg.to_owned();
+                break;
+            }
+            if let Some(second) = second {
+                argument = second.to_owned();
+                used_second = true;
+                break;
+            }
+            return Err(UsageError::MissingArgument(first.to_owned()));
+        }
+        match first {
+            // profiles are lowercase (`ps_5_0`), the profile table and the compiler only know
+            // them that way, so `/T PS_5_0` is taken as `/T ps_5_0`
+            "T" => Ok((Opts::Model(argument.to_ascii_lowercase()), used_second)),
+            "D" => {
+                // like fxc, /DFOO defines FOO as 1 so both `#ifdef FOO` and `#if FOO` see it,
+                // while /DFOO= defines it as empty (true for #ifdef, an error for #if)
+                let (name, value) = argument.split_once('=').unwrap_or((&argument, "1"));
+                let name = CString::new(name)
+                    .map_err(|_| UsageError::InvalidArgument(argument.clone()))?;
+                let value = CString::new(value)
+                    .map_err(|_| UsageError::InvalidArgument(argument.clone()))?;
+                Ok((Opts::Define(name, value), used_second))
+            }
+            "E" => {
+                let entry_point = CString::new(argument.as_str())
+                    .map_err(|_| UsageError::InvalidArgument(argument))?;
+                Ok((Opts::EntryPointName(entry_point), used_second))
+            }
+            "Fh" => Ok((Opts::OutputFile(argument), used_second)),
+            "Fo" => Ok((Opts::ObjectFile(argument), used_second)),
+            "Fc" => Ok((Opts::AssemblyFile(argument), used_second)),
+            "setprivate" => Ok((Opts::SetPrivate(argument), used_second)),
+            "getprivate" => Ok((Opts::GetPrivate(argument), used_second)),
+            "setrootsignature" => Ok((Opts::SetRootSignature(argument), used_second)),
+            "extractrootsignature" => Ok((Opts::ExtractRootSignature(argument), used_second)),
+            "verifyrootsignature" => Ok((Opts::VerifyRootSignature(argument), used_second)),
+            "Vn" => Ok((Opts::VariableName(argument), used_second)),
+            "P" | "Fi" => Ok((Opts::Preprocess(argument), used_second)),
+            "I" => Ok((Opts::IncludeDir(argument), used_second)),
+            "-emit-signature" => Ok((Opts::EmitSignature(argument), used_second)),
+            "-reflect-json" => Ok((Opts::ReflectJson(argument), used_second)),
+            "-emit-descriptor-map" => Ok((Opts::EmitDescriptorMap(argument), used_second)),
+            "-secondary" => Ok((Opts::SecondaryData(argument), used_second)),
+            "-expect-profile" => Ok((
+                Opts::ExpectProfile(argument.to_ascii_lowercase()),
+                used_second,
+            )),
+            "-compile-db" => Ok((Opts::CompileDb(argument), used_second)),
+            "-meta" => Ok((Opts::Meta(argument), used_second)),
+            "-compare-precision" => Ok((Opts::ComparePrecision(argument), used_second)),
+            "MF" | "-depfile" => Ok((Opts::DepFile(argument), used_second)),
+            "-dep-relative-to" => Ok((Opts::DepRelativeTo(argument), used_second)),
+            "-emit-rc" => Ok((Opts::EmitRc(argument), used_second)),
+            "-emit-pso-desc" => Ok((Opts::EmitPsoDesc(argument), used_second)),
+            "-techniques" => Ok((Opts::Techniques(argument), used_second)),
+            "-emit-bindings-hlsl" => Ok((Opts::EmitBindingsHlsl(argument), used_second)),
+            "-errors-blob" => Ok((Opts::ErrorsBlob(argument), used_second)),
+            "-files-from" => Ok((Opts::FilesFrom(argument), used_second)),
+            "-manifest" => Ok((Opts::Manifest(argument), used_second)),
+            "-keep-temps" => Ok((Opts::KeepTemps(argument), used_second)),
+            "-emit-enum" => Ok((Opts::EmitEnum(argument), used_second)),
+            "-golden" => Ok((Opts::Golden(argument), used_second)),
+            "-emit-asm-inc" => Ok((Opts::EmitAsmInc(argument), used_second)),
+            "-asm-flavor" => match argument.as_str() {
+                "nasm" => Ok((Opts::AsmFlavor(AsmFlavor::Nasm), used_second)),
+                "masm" => Ok((Opts::AsmFlavor(AsmFlavor::Masm), used_second)),
+                _ => Err(UsageError::InvalidArgument(argument)),
+            },
+            // `shader_` and `shader` both give `shader_main`
+            "-default-prefix" => match argument.trim_end_matches('_') {
+                "" => Err(UsageError::InvalidArgument(argument)),
+                prefix => Ok((Opts::DefaultPrefix(prefix.to_owned()), used_second)),
+            },
+            "-note" => Ok((Opts::Note(argument), used_second)),
+            "-forbid" if argument.is_empty() => Err(UsageError::InvalidArgument(argument)),
+            "-forbid" => Ok((Opts::Forbid(argument), used_second)),
+            "-entry-pattern" => {
+                let pattern = Pattern::new(&argument)
+                    .map_err(|err| UsageError::InvalidArgument(format!("{argument}: {err}")))?;
+                Ok((Opts::EntryPattern(pattern), used_second))
+            }
+            "-warnings-to" => match argument.as_str() {
+                "stdout" => Ok((Opts::WarningsTo(WarningStream::Stdout), used_second)),
+                "stderr" => Ok((Opts::WarningsTo(WarningStream::Stderr), used_second)),
+                _ => Err(UsageError::InvalidArgument(argument)),
+            },
+            "-lang" => match argument.as_str() {
+                "c" => Ok((Opts::Language(OutputLanguage::C), used_second)),
+                "csharp" => Ok((Opts::Language(OutputLanguage::CSharp), used_second)),
+                _ => Err(UsageError::InvalidArgument(argument)),
+            },
+            "-model-alias" => match argument.split_once('=') {
+                Some((alias, model)) if !alias.is_empty() && !model.is_empty() => Ok((
+                    Opts::ModelAlias(alias.to_ascii_lowercase(), model.to_ascii_lowercase()),
+                    used_second,
+                )),
+                _ => Err(UsageError::InvalidArgument(argument)),
+            },
+            "-expect-binding" => {
+                let binding = ExpectedBinding::parse(&argument)
+                    .ok_or(UsageError::InvalidArgument(argument))?;
+                Ok((Opts::ExpectBinding(binding), used_second))
+            }
+            "-split-header" => {
+                let parts = argument
+                    .parse()
+                    .ok()
+                    .filter(|parts| (1..=MAX_HEADER_PARTS).contains(parts))
+                    .ok_or(UsageError::InvalidArgument(argument))?;
+                Ok((Opts::SplitHeader(parts), used_second))
+            }
+            "-max-instructions" => {
+                let max_instructions = argument
+                    .parse()
+                    .map_err(|_| UsageError::InvalidArgument(argument))?;
+                Ok((Opts::MaxInstructions(max_instructions), used_second))
+            }
+            "-max-warnings" => {
+                let max_warnings = argument
+                    .parse()
+                    .map_err(|_| UsageError::InvalidArgument(argument))?;
+                Ok((Opts::MaxWarnings(max_warnings), used_second))
+            }
+            "-timeout" => match argument.parse() {
+                Ok(0) | Err(_) => Err(UsageError::InvalidArgument(argument)),
+                Ok(seconds) => Ok((Opts::Timeout(seconds), used_second)),
+            },
+            "-max-bytes" => {
+                let max_bytes = argument
+                    .parse()
+                    .map_err(|_| UsageError::InvalidArgument(argument))?;
+                Ok((Opts::MaxBytes(max_bytes), used_second))
+            }
+            "-byte-width" => {
+                let width = argument
+                    .parse()
+                    .ok()
+                    .filter(|width| *width <= MAX_LAYOUT_WIDTH)
+                    .ok_or(UsageError::InvalidArgument(argument))?;
+                Ok((Opts::ByteWidth(width), used_second))
+            }
+            "-indent" => {
+                let indent = match argument.parse() {
+                    Ok(spaces) if spaces <= MAX_LAYOUT_WIDTH => " ".repeat(spaces),
+                    Err(_) if argument == "tab" => "\t".to_owned(),
+                    _ => return Err(UsageError::InvalidArgument(argument)),
+                };
+                Ok((Opts::Indent(indent), used_second))
+            }
+            "-cbuffer-budget" => {
+                let budget = argument
+                    .parse()
+                    .map_err(|_| UsageError::InvalidArgument(argument))?;
+                Ok((Opts::CbufferBudget(budget), used_second))
+            }
+            "-hexdump-length" => {
+                let length = argument
+                    .parse()
+                    .map_err(|_| UsageError::InvalidArgument(argument))?;
+                Ok((Opts::HexdumpLength(length), used_second))
+            }
+            "-include-ext" => {
+                let extensions = argument
+                    .split(',')
+                    .map(|extension| extension.trim_start_matches('.').to_owned())
+                    .filter(|extension| !extension.is_empty())
+                    .collect();
+                Ok((Opts::IncludeExtensions(extensions), used_second))
+            }
+            _ => Err(UsageError::UnknownArgument(first.to_owned())),
+        }
+    }
+
+    /// Options that only affect compiling from source, as opposed to processing the compiled
+    /// shader.
+    fn is_compile_only(&self) -> bool {
+        matches!(
+            self,
+            Opts::AllResourcesBound
+                | Opts::Define(..)
+                | Opts::EntryPointName(_)
+                | Opts::UnboundedDescriptorTables
+                | Opts::BackwardsCompatibility
+                | Opts::EnableStrictness
+                | Opts::AvoidFlowControl
+                | Opts::EnableIEEEStrictness
+                | Opts::PartialPrecision
+                | Opts::MatchUAVs
+                | Opts::MergeUAVs
+                | Opts::IncludeDir(_)
+                | Opts::DisableOptimizations
+                | Opts::DisablePreshaders
+                | Opts::OptimizationLevel0
+                | Opts::OptimizationLevel1
+                | Opts::OptimizationLevel2
+                | Opts::OptimizationLevel3
+                | Opts::ResourceMayAlias
+                | Opts::SkipValidation
+                | Opts::OutputIncludeProcessDetails
+                | Opts::WarningsAsErrors
+                | Opts::DebugInformation
+                | Opts::PackMatrixColumnMajor
+                | Opts::PackMatrixRowMajor
+                | Opts::IncludeExtensions(_)
+                | Opts::SecondaryData(_)
+                | Opts::NoSourceRelativeIncludes
+                | Opts::EntryPattern(_)
+        )
+    }
+
+    /// Serializes the option back into the syntax the real fxc accepts.
+    /// Returns None for options that only exist in fxc2.
+    fn to_fxc(&self) -> Option<String> {
+        fn quote(arg: &str) -> String {
+            if arg.contains(' ') {
+                format!("\"{arg}\"")
+            } else {
+                arg.to_owned()
+            }
+        }
+        let arg = match self {
+            Opts::Model(model) => format!("/T {}", quote(model)),
+            Opts::Help => "/?".to_owned(),
+            Opts::AllResourcesBound => "/all_resources_bound".to_owned(),
+            Opts::Define(name, value) => format!(
+                "/D {}",
+                quote(&format!(
+                    "{}={}",
+                    name.to_string_lossy(),
+                    value.to_string_lossy()
+                ))
+            ),
+            Opts::EntryPointName(entry_point) => {
+                format!("/E {}", quote(&entry_point.to_string_lossy()))
+            }
+            Opts::UnboundedDescriptorTables => "/enable_unbounded_descriptor_tables".to_owned(),
+            Opts::DumpBin => "/dumpbin".to_owned(),
+            Opts::Compress => "/compress".to_owned(),
+            Opts::Decompress => "/decompress".to_owned(),
+            Opts::OutputFile(output_file) => format!("/Fh {}", quote(output_file)),
+            Opts::ObjectFile(object_file) => format!("/Fo {}", quote(object_file)),
+            Opts::AssemblyFile(assembly_file) => format!("/Fc {}", quote(assembly_file)),
+            Opts::SetPrivate(private_file) => format!("/setprivate {}", quote(private_file)),
+            Opts::GetPrivate(private_file) => format!("/getprivate {}", quote(private_file)),
+            Opts::SetRootSignature(root_signature_file) => {
+                format!("/setrootsignature {}", quote(root_signature_file))
+            }
+            Opts::ExtractRootSignature(root_signature_file) => {
+                format!("/extractrootsignature {}", quote(root_signature_file))
+            }
+            Opts::VerifyRootSignature(root_signature_file) => {
+                format!("/verifyrootsignature {}", quote(root_signature_file))
+            }
+            Opts::BackwardsCompatibility => "/Gec".to_owned(),
+            Opts::EnableStrictness => "/Ges".to_owned(),
+            Opts::AvoidFlowControl => "/Gfa".to_owned(),
+            Opts::EnableIEEEStrictness => "/Gis".to_owned(),
+            Opts::PartialPrecision => "/Gpp".to_owned(),
+            Opts::MatchUAVs => "/matchUAVs".to_owned(),
+            Opts::MergeUAVs => "/mergeUAVs".to_owned(),
+            Opts::NoLogo => "/nologo".to_owned(),
+            Opts::DisableOptimizations => "/Od".to_owned(),
+            Opts::DisablePreshaders => "/Op".to_owned(),
+            Opts::Preprocess(preprocess_file) => format!("/P {}", quote(preprocess_file)),
+            Opts::IncludeDir(include_dir) => format!("/I {}", quote(include_dir)),
+            Opts::OptimizationLevel0 => "/O0".to_owned(),
+            Opts::OptimizationLevel1 => "/O1".to_owned(),
+            Opts::OptimizationLevel2 => "/O2".to_owned(),
+            Opts::OptimizationLevel3 => "/O3".to_owned(),
+            Opts::StripRootSignature => "/Qstrip_rootsignature".to_owned(),
+            Opts::StripReflect => "/Qstrip_reflect".to_owned(),
+            Opts::StripDebug => "/Qstrip_debug".to_owned(),
+            Opts::StripPrivate => "/Qstrip_priv".to_owned(),
+            Opts::ResourceMayAlias => "/res_may_alias".to_owned(),
+            Opts::SkipValidation => "/Vd".to_owned(),
+            Opts::OutputIncludeProcessDetails => "/Vi".to_owned(),
+            Opts::VariableName(variable_name) => format!("/Vn {}", quote(variable_name)),
+            Opts::WarningsAsErrors => "/WX".to_owned(),
+            Opts::DebugInformation => "/Zi".to_owned(),
+            Opts::PackMatrixColumnMajor => "/Zpc".to_owned(),
+            Opts::PackMatrixRowMajor => "/Zpr".to_owned(),
+            Opts::InputFile(input_file) => quote(input_file),
+            Opts::EchoFxc
+            | Opts::EmitSignature(_)
+            | Opts::WithOutputSignature
+            | Opts::Minimal
+            | Opts::IncludeExtensions(_)
+            | Opts::ReflectJson(_)
+            | Opts::StrictDefines
+            | Opts::ExpectBinding(_)
+            | Opts::Hexdump
+            | Opts::HexdumpLength(_)
+            | Opts::EmitLoader
+            | Opts::SecondaryData(_)
+            | Opts::WhyFailed
+            | Opts::StripLineDirectives
+            | Opts::ExpectProfile(_)
+            | Opts::Compact
+            | Opts::CompileDb(_)
+            | Opts::Language(_)
+            | Opts::SplitHeader(_)
+            | Opts::CbufferBudget(_)
+            | Opts::CbufferBudgetError
+            | Opts::SortDefines
+            | Opts::EmitDescriptorMap(_)
+            | Opts::NoSourceRelativeIncludes
+            | Opts::Clipboard
+            | Opts::EntryPattern(_)
+            | Opts::Summary
+            | Opts::Forbid(_)
+            | Opts::Meta(_)
+            | Opts::MetaTimestamp
+            | Opts::ShowHash
+            | Opts::ComparePrecision(_)
+            | Opts::Strict
+            | Opts::DepFile(_)
+            | Opts::DepRelativeTo(_)
+            | Opts::EmitRc(_)
+            | Opts::ListProfiles
+            | Opts::Note(_)
+            | Opts::ShowNote
+            | Opts::Threadgroup
+            | Opts::ByteWidth(_)
+            | Opts::Indent(_)
+            | Opts::Check
+            | Opts::MaxBytes(_)
+            | Opts::InferFromOutput
+            | Opts::Trace
+            | Opts::WarningsTo(_)
+            | Opts::ModelAlias(..)
+            | Opts::DumpSections
+            | Opts::LengthPrefixed
+            | Opts::EmitPsoDesc(_)
+            | Opts::NormalizeLineEndings
+            | Opts::MaxInstructions(_)
+            | Opts::Techniques(_)
+            | Opts::StrictArgs
+            | Opts::EmitBindingsHlsl(_)
+            | Opts::MaxWarnings(_)
+            | Opts::Porcelain
+            | Opts::ErrorsBlob(_)
+            | Opts::FilesFrom(_)
+            | Opts::Null
+            | Opts::ProfileTimings
+            | Opts::EmbedDisassembly
+            | Opts::DefaultPrefix(_)
+            | Opts::WriteIfChanged
+            | Opts::Manifest(_)
+            | Opts::KeepTemps(_)
+            | Opts::EmitEnum(_)
+            | Opts::SplitDebug
+            | Opts::Golden(_)
+            | Opts::UpdateGolden
+            | Opts::ShowDefines
+            | Opts::EmitAsmInc(_)
+            | Opts::AsmFlavor(_)
+            | Opts::Timeout(_) => return None,
+        };
+        Some(arg)
+    }
+}
+
+/// The language of the /Fh output.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OutputLanguage {
+    /// A `const BYTE` array, like fxc writes
+    C,
+    /// A `byte[]` field in a `Shaders` partial class
+    CSharp,
+    /// An `inline constexpr std::array<std::uint8_t, N>`, for C++17
+    StdArray,
+}
+
+/// The assembler syntax of the --emit-asm-inc output.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AsmFlavor {
+    /// `db` lines, for NASM and YASM
+    Nasm,
+    /// `BYTE` declarations, for MASM
+    Masm,
+}
+
+/// Where warnings are printed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WarningStream {
+    Stdout,
+    Stderr,
+}
+
+/// A resource binding the compiled shader must have, given as `NAME=slot` or `NAME=slot:space`.
+#[derive(Clone)]
+pub struct ExpectedBinding {
+    pub name: String,
+    pub slot: u32,
+    /// None if any space is fine
+    pub space: Option<u32>,
+}
+
+impl ExpectedBinding {
+    fn parse(arg: &str) -> Option<ExpectedBinding> {
+        let (name, binding) = arg.split_once('=')?;
+        let (slot, space) = match binding.split_once(':') {
+            Some((slot, space)) => (slot, Some(space.parse().ok()?)),
+            None => (binding, None),
+        };
+        Some(ExpectedBinding {
+            name: name.to_owned(),
+            slot: slot.parse().ok()?,
+            space,
+        })
+    }
+}
+
+/// Everything fxc2 needs to know about an invocation, as parsed from the command line.
+#[derive(Clone)]
+pub struct ParsedOptions {
+    pub model: String,
+    pub entry_point: CString,
+    /// The name of the shader in the generated sources (the /Fh header, --emit-asm-inc, ...). Empty
+    /// when nothing generated is asked for, e.g. with only /Fo.
+    pub variable_name: String,
+    pub output_file: String,
+    /// Write the compiled shader as is (a `.cso`) here, on its own or next to the /Fh header
+    pub object_file: Option<String>,
+    /// Write the disassembly of the shader here, also of a /dumpbin input
+    pub assembly_file: Option<String>,
+    /// Store the contents of this file in the shader's private data part, replacing what was there
+    pub set_private_file: Option<String>,
+    /// Write the shader's private data part here, also of a /dumpbin input
+    pub get_private_file: Option<String>,
+    /// Embed the root signature in this file (a compiled `rootsig_1_*`) in the shader, replacing
+    /// the one it had
+    pub set_root_signature_file: Option<String>,
+    /// Write the shader's root signature here, also of a /dumpbin input
+    pub extract_root_signature_file: Option<String>,
+    /// Fail unless the root signature in this file covers every resource the shader binds
+    pub verify_root_signature_file: Option<String>,
+    /// The /D macros in the order given, as (name, value). A /D without `=` has the value "1".
+    pub defines: Vec<(CString, CString)>,
+    pub input_file: String,
+    /// Every input file when several are given, which are compiled one by one like the files of
+    /// --files-from; `input_file` is empty then
+    pub input_files: Vec<String>,
+    pub flags1: u32,
+    pub output_include_details: bool,
+    pub echo_fxc: bool,
+    pub signature_file: Option<String>,
+    pub with_output_signature: bool,
+    /// Strip everything but the shader itself from the /Fo output
+    pub minimal: bool,
+    /// Extensions to try, in order, for includes that can't be found as named
+    pub include_extensions: Vec<String>,
+    pub reflect_json_file: Option<String>,
+    pub expected_bindings: Vec<ExpectedBinding>,
+    /// Print a hexdump of the output to stdout, limited to the given number of bytes if any
+    pub hexdump: Option<Option<usize>>,
+    /// Add a D3D11 shader creation function to the /Fh header
+    pub emit_loader: bool,
+    /// D3DCOMPILE_SECDATA_* flags, /matchUAVs and /mergeUAVs need the --secondary blob
+    pub secondary_data_flags: u32,
+    pub secondary_data_file: Option<String>,
+    /// Explain the HRESULT when compiling fails
+    pub why_failed: bool,
+    /// Only preprocess the input, writing the result to this file (/P or /Fi)
+    pub preprocess_file: Option<String>,
+    /// Remove `#line` directives and trailing whitespace from the /P output
+    pub strip_line_directives: bool,
+    /// The input is a compiled blob to inspect rather than source to compile
+    pub dump_bin: bool,
+    /// Pack the compiled shaders of the input files into one compressed blob written to /Fo
+    pub compress: bool,
+    /// Unpack the compressed blob of the first input file into the other input files, in the
+    /// order they were packed
+    pub decompress: bool,
+    pub expected_profile: Option<String>,
+    /// Write the /Fh array without padding, wrapped at a fixed width
+    pub compact: bool,
+    /// Remove the root signature part from the compiled shader
+    pub strip_root_signature: bool,
+    /// The D3DCOMPILER_STRIP_FLAGS of the /Qstrip_* options, applied to every output
+    pub strip_flags: u32,
+    pub language: OutputLanguage,
+    /// Number of files to split the /Fh array into, 1 to keep it whole
+    pub header_parts: usize,
+    /// Maximum total size of the constant buffers in bytes, warn if exceeded
+    pub cbuffer_budget: Option<u32>,
+    /// Fail instead of warning when over the constant buffer budget
+    pub cbuffer_budget_error: bool,
+    /// Show the defines sorted by name in logs, the compiler still gets them in the given order
+    pub sort_defines: bool,
+    pub descriptor_map_file: Option<String>,
+    /// Directories searched for includes, in order
+    pub include_dirs: Vec<String>,
+    /// Resolve includes relative to the including file and the source file before the /I directories
+    pub source_relative_includes: bool,
+    /// Copy the hexdump, or else the /Fh header, to the clipboard instead of writing it out
+    pub clipboard: bool,
+    /// Preprocess and print an overview of the declarations instead of compiling
+    pub summary: bool,
+    /// Instructions the disassembly must not contain
+    pub forbidden: Vec<String>,
+    pub meta_file: Option<String>,
+    /// Record the time of the compile in the --meta and --manifest files
+    pub meta_timestamp: bool,
+    /// Print the hash stored in the container header
+    pub show_hash: bool,
+    /// Compare the precision of this compiled shader's instructions to the input's, instead of
+    /// compiling
+    pub compare_precision: Option<String>,
+    /// Fail if --compare-precision finds differences
+    pub strict: bool,
+    /// Write a make rule listing the input and the files it included here (-MF or --depfile)
+    pub dep_file: Option<String>,
+    /// Write the paths in the dependency file relative to this directory
+    pub dep_relative_to: Option<String>,
+    /// Write a resource script embedding the shader (or referencing the /Fo file) here
+    pub rc_file: Option<String>,
+    /// List the known profiles instead of compiling
+    pub list_profiles: bool,
+    /// Text to store in the shader's private data part. There's only one such part, so this
+    /// replaces whatever private data the shader had.
+    pub note: Option<String>,
+    /// Print the text stored in the shader's private data part
+    pub show_note: bool,
+    /// Print a compute shader's thread group size, and declare it as `<variable_name>_threads`
+    /// in the header
+    pub threadgroup: bool,
+    /// The minimum width of every byte in the header, 4 by default
+    pub byte_width: usize,
+    /// Written at the start of every row of bytes in the header: a number of spaces, or a tab
+    pub indent: String,
+    /// Only compile to check for errors, without writing any output
+    pub check: bool,
+    /// Fail if the shader is bigger than this, checked on the /Fo object after --minimal
+    /// stripping if there is one
+    pub max_bytes: Option<usize>,
+    /// Log every compiler API call and its result to stderr
+    pub trace: bool,
+    /// Where fxc2's and the compiler's warnings go, stderr by default
+    pub warnings_to: WarningStream,
+    /// Print the offset and size of every chunk of the compiled shader
+    pub dump_sections: bool,
+    /// Write the /Fo object preceded by its size as a little endian u32
+    pub length_prefixed: bool,
+    /// Write what a compute shader needs from its pipeline as a C header here
+    pub pso_desc_file: Option<String>,
+    /// Convert the input's CRLF and CR line endings to LF before compiling it
+    pub normalize_line_endings: bool,
+    /// Fail if the compiled shader has more instructions than this, as counted by reflection
+    pub max_instructions: Option<u32>,
+    /// Compile every entry point and profile listed in this file instead of /E and /T
+    pub techniques_file: Option<String>,
+    /// Only take arguments with a known input extension, or given after `--`, as the input file
+    pub strict_args: bool,
+    /// Write the register of every resource the shader binds as HLSL macros here
+    pub bindings_hlsl_file: Option<String>,
+    /// Fail if the compiler reports more warnings than this
+    pub max_warnings: Option<usize>,
+    /// Print only `FXC2-OK <output> <bytes> <md5>` or `FXC2-FAIL <reason>` to stdout, for tools
+    pub porcelain: bool,
+    /// Write the compiler's error and warning blob here, byte for byte
+    pub errors_blob_file: Option<String>,
+    /// Compile every input file listed in this file (`-` for stdin) instead of the input file
+    pub files_from: Option<String>,
+    /// The --files-from list is separated by NUL bytes instead of lines
+    pub null_separated: bool,
+    /// Print how long reading, preprocessing, compiling, stripping and writing took
+    pub profile_timings: bool,
+    /// Append the disassembly to the /Fh header, in a comment
+    pub embed_disassembly: bool,
+    /// The variable name prefix for profiles without one of their own, instead of `g`
+    pub default_prefix: String,
+    /// Leave /Fh and /Fo untouched when they already hold what would be written
+    pub write_if_changed: bool,
+    /// List every file a batch wrote in this JSON file
+    pub manifest_file: Option<String>,
+    /// Also write the preprocessed input to this directory, which takes an extra preprocessing
+    /// pass over the input and its includes
+    pub temps_dir: Option<String>,
+    /// Write an enum of every shader a batch compiled, with a table of their bytes, here
+    pub enum_file: Option<String>,
+    /// Move the /Zi debug information out of the /Fo object into a .pdb next to it
+    pub split_debug: bool,
+    /// Compare the shader against this golden blob, ignoring the parts that change between builds
+    pub golden_file: Option<String>,
+    /// Overwrite the golden blob with the shader instead of comparing them
+    pub update_golden: bool,
+    /// Print the defines in the order they're resolved, with where each came from
+    pub show_defines: bool,
+    /// Write the shader as assembler data declarations, labeled with the variable name, here
+    pub asm_inc_file: Option<String>,
+    pub asm_flavor: AsmFlavor,
+    /// Give up on a compile still running after this many seconds
+    pub timeout: Option<u64>,
+    /// Problems that don't prevent compiling but are likely mistakes.
+    pub warnings: Vec<String>,
+    /// The invocation re-serialized into the real fxc's syntax, fxc2-only options excluded.
+    pub fxc_args: Vec<String>,
+}
+
+/// A `--compile-db` invocation: the arguments for `input_file` come from the database.
+pub struct CompileDbRequest {
+    pub db_file: String,
+    pub input_file: String,
+    /// The other arguments, applied after the database's
+    pub extra_args: Vec<String>,
+}
+
+/// Splits an output file named `<name>.<profile>.<entry>.<extension>`, e.g. `foo.ps_5_0.main.h`,
+/// into its profile and entry point. The profile has to be one of the known ones.
+fn infer_from_output(output_file: &str) -> Option<(&str, &str)> {
+    let file_name = Path::new(output_file).file_name()?.to_str()?;
+    let mut parts = file_name.rsplitn(4, '.');
+    let (_extension, entry_point, profile, _name) =
+        (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+    (PROFILES.contains(&profile.to_ascii_lowercase().as_str()) && !entry_point.is_empty())
+        .then_some((profile, entry_point))
+}
+
+/// The prefix of variable names derived for profiles that aren't in the table, unless
+/// --default-prefix changes it.
+pub const DEFAULT_PREFIX: &str = "g";
+
+/// The prefix of the variable name derived for a profile, the entry point follows it after a `_`.
+/// Profiles that aren't in the table get `default_prefix`.
+pub fn variable_prefix<'a>(model: &str, default_prefix: &'a str) -> &'a str {
+    match PROFILE_PREFIX_TABLE.iter().find(|i| i.name == model) {
+        Some(name) => name.prefix,
+        None => default_prefix,
+    }
+}
+
+/// The `/D` macros among the arguments, in order, to tell which of the defines of a merged
+/// argument list came from them. Stops at `--` or at the first argument that doesn't parse.
+pub fn defines(args: &[String]) -> Vec<(CString, CString)> {
+    let mut defines = Vec::new();
+    let mut i = 0;
+    while i < args.len() && args[i] != "--" {
+        let Ok((opt, used_second)) = Opts::parse(&args[i], args.get(i + 1).map(|x| x.as_str()))
+        else {
+            break;
+        };
+        if let Opts::Define(name, value) = opt {
+            defines.push((name, value));
+        }
+        i += if used_second { 2 } else { 1 };
+    }
+    defines
+}
+
+/// The arguments without the input files, for compiling several input files one by one.
+pub fn without_input_files(args: &[String]) -> Vec<String> {
+    let mut options = Vec::with_capacity(args.len());
+    let mut i = 0;
+    // everything after `--` is an input file
+    while i < args.len() && args[i] != "--" {
+        let used = match Opts::parse(&args[i], args.get(i + 1).map(|x| x.as_str())) {
+            Ok((Opts::InputFile(_), _)) => {
+                i += 1;
+                continue;
+            }
+            Ok((_, true)) => 2,
+            _ => 1,
+        };
+        options.extend_from_slice(&args[i..i + used]);
+        i += used;
+    }
+    options
+}
+
+/// Takes `--compile-db` and the input file out of the arguments, if `--compile-db` is given.
+pub fn split_compile_db(args: &[String]) -> Result<Option<CompileDbRequest>, UsageError> {
+    let mut db_file = None;
+    let mut input_file = None;
+    let mut extra_args = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--" {
+            // the rest are input files, even if they look like options
+            for file in &args[i + 1..] {
+                if input_file.replace(file.clone()).is_some() {
+                    return Err(UsageError::TooManyArguments);
+                }
+            }
+            break;
+        }
+        let (opt, used_second) = Opts::parse(&args[i], args.get(i + 1).map(|x| x.as_str()))?;
+        let used = if used_second { 2 } else { 1 };
+        match opt {
+            Opts::CompileDb(file) => db_file = Some(file),
+            Opts::InputFile(file) => {
+                if input_file.replace(file).is_some() {
+                    return Err(UsageError::TooManyArguments);
+                }
+            }
+            _ => extra_args.extend_from_slice(&args[i..i + used]),
+        }
+        i += used;
+    }
+    let Some(db_file) = db_file else {
+        return Ok(None);
+    };
+    let input_file = input_file.ok_or(UsageError::MissingArgument("input file".to_owned()))?;
+    Ok(Some(CompileDbRequest {
+        db_file,
+        input_file,
+        extra_args,
+    }))
+}
+
+/// Replaces every `@file` argument with the arguments listed in that file, read with `read`.
+///
+/// The arguments in a response file are separated by whitespace, and can be quoted with `"` to
+/// contain it. Response files can name other response files, which are expanded in place too (a
+/// file naming itself, directly or not, is an error), and arguments after `--` are never
+/// expanded. Since the arguments are expanded in place, options from response files follow the
+/// same precedence as on the command line (see [`parse_args`]): defines and include directories
+/// from every file accumulate in order, and for scalar options the last one wins, whether it came
+/// from a file or the command line.
+pub fn expand_response_files(
+    args: &[String],
+    read: impl Fn(&str) -> std::io::Result<String>,
+) -> Result<Vec<String>, UsageError> {
+    let mut expanded = Vec::with_capacity(args.len());
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            expanded.push(arg.clone());
+            expanded.extend(args.by_ref().cloned());
+            break;
+        }
+        match arg.strip_prefix('@') {
+            Some(file) => expand_response_file(file, &read, &mut Vec::new(), &mut expanded)?,
+            None => expanded.push(arg.clone()),
+        }
+    }
+    Ok(expanded)
+}
+
+/// Appends the arguments of the response file `file` to `expanded`, expanding the response files
+/// it names. `parents` are the response files being expanded that led to this one.
+fn expand_response_file(
+    file: &str,
+    read: &impl Fn(&str) -> std::io::Result<String>,
+    parents: &mut Vec<String>,
+    expanded: &mut Vec<String>,
+) -> Result<(), UsageError> {
+    if parents.iter().any(|parent| parent == file) {
+        return Err(UsageError::InvalidResponseFile(
+            file.to_owned(),
+            "it names itself through other response files".to_owned(),
+        ));
+    }
+    let text = read(file)
+        .map_err(|err| UsageError::InvalidResponseFile(file.to_owned(), err.to_string()))?;
+    let mut args = Vec::new();
+    let mut current = None::<String>;
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => args.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        return Err(UsageError::InvalidResponseFile(
+            file.to_owned(),
+            "unterminated quote".to_owned(),
+        ));
+    }
+    args.extend(current);
+
+    parents.push(file.to_owned());
+    for arg in args {
+        match arg.strip_prefix('@') {
+            Some(nested) => expand_response_file(nested, read, parents, expanded)?,
+            None => expanded.push(arg),
+        }
+    }
+    parents.pop();
+    Ok(())
+}
+
+/// Turns the flag names of a `#pragma fxc_flags` (see [`crate::pragma`]) into the arguments to
+/// put before the command line's, e.g. `WX` into `/WX`. Only fxc options taking no argument are
+/// accepted, `file` names the source in the error for any other name.
+pub fn pragma_args(file: &str, flags: &[&str]) -> Result<Vec<String>, UsageError> {
+    flags
+        .iter()
+        .map(|flag| {
+            let arg = format!("/{flag}");
+            match Opts::parse(&arg, None) {
+                Ok((Opts::Help | Opts::InputFile(_), _)) | Err(_) => {}
+                Ok((opt, _)) if opt.to_fxc().as_ref() == Some(&arg) => return Ok(arg),
+                Ok(_) => {}
+            }
+            Err(UsageError::InvalidPragma(
+                file.to_owned(),
+                format!("unknown flag '{flag}'"),
+            ))
+        })
+        .collect()
+}
+
+/// Parses the command line arguments (without the program name).
+/// This does not touch the filesystem or the environment and never panics, whatever the input.
+///
+/// Repeated options follow one precedence model:
+/// - scalar options (`/T`, `/E`, `/Fh`, `/Fo`, `/Vn`, `/O0`-`/O3`, `/Zpc`/`/Zpr`, ...) are last-wins,
+///   so a later `/O1` overrides an earlier `/O3`
+/// - additive options (`/D`, `--include-ext`, `--expect-binding`, ...) accumulate in order
+/// - switches (`/Gfa`, `/WX`, `/Zi`, ...) are on once given, repeating them is harmless
+pub fn parse_args(args: &[String]) -> Result<ParsedOptions, UsageError> {
+    parse_args_with_env(args, |_| None)
+}
+
+/// The `.pdb` that --split-debug writes next to the object file.
+pub fn debug_file(object_file: &str) -> PathBuf {
+    Path::new(object_file).with_extension("pdb")
+}
+
+/// The file that holds part `part` of a --split-header header: `<name>.part<part>.<ext>`.
+pub fn header_part_file(output_file: &str, part: usize) -> PathBuf {
+    let path = Path::new(output_file);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    path.with_file_name(format!("{stem}.part{part}{extension}"))
+}
+
+/// Where --keep-temps keeps the preprocessed input: `<temps_dir>/<input file name>.i`.
+pub fn preprocessed_temp_file(temps_dir: &str, input_file: &str) -> PathBuf {
+    let file_name = Path::new(input_file)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    Path::new(temps_dir).join(format!("{file_name}.i"))
+}
+
+/// Fails if two outputs, given or derived (the --split-debug `.pdb`, the --split-header parts and
+/// the --keep-temps file), are the same file, one would overwrite the other. Unlike [`parse_args`]
+/// this resolves paths, so it reads the current directory and the filesystem.
+pub fn check_output_paths(opts: &ParsedOptions) -> Result<(), UsageError> {
+    let given = [
+        (
+            "/Fh",
+            Some(&opts.output_file).filter(|file| !file.is_empty()),
+        ),
+        ("/Fo", opts.object_file.as_ref()),
+        ("/Fc", opts.assembly_file.as_ref()),
+        ("/getprivate", opts.get_private_file.as_ref()),
+        (
+            "/extractrootsignature",
+            opts.extract_root_signature_file.as_ref(),
+        ),
+        ("/P", opts.preprocess_file.as_ref()),
+        ("--emit-signature", opts.signature_file.as_ref()),
+        ("--reflect-json", opts.reflect_json_file.as_ref()),
+        ("--emit-descriptor-map", opts.descriptor_map_file.as_ref()),
+        ("--meta", opts.meta_file.as_ref()),
+        ("-MF", opts.dep_file.as_ref()),
+        ("--emit-rc", opts.rc_file.as_ref()),
+        ("--emit-asm-inc", opts.asm_inc_file.as_ref()),
+        ("--emit-pso-desc", opts.pso_desc_file.as_ref()),
+        ("--emit-bindings-hlsl", opts.bindings_hlsl_file.as_ref()),
+        ("--errors-blob", opts.errors_blob_file.as_ref()),
+        ("--manifest", opts.manifest_file.as_ref()),
+        ("--emit-enum", opts.enum_file.as_ref()),
+        (
+            "--update-golden",
+            opts.golden_file.as_ref().filter(|_| opts.update_golden),
+        ),
+    ];
+    let mut outputs = given
+        .into_iter()
+        .filter_map(|(option, file)| Some((option, PathBuf::from(file?))))
+        .collect::<Vec<_>>();
+    if let Some(object_file) = opts.object_file.as_ref().filter(|_| opts.split_debug) {
+        outputs.push(("--split-debug", debug_file(object_file)));
+    }
+    if opts.header_parts > 1 && !opts.output_file.is_empty() {
+        for part in 0..opts.header_parts {
+            outputs.push(("--split-header", header_part_file(&opts.output_file, part)));
+        }
+    }
+    if let Some(temps_dir) = opts
+        .temps_dir
+        .as_ref()
+        .filter(|_| !opts.input_file.is_empty())
+    {
+        outputs.push((
+            "--keep-temps",
+            preprocessed_temp_file(temps_dir, &opts.input_file),
+        ));
+    }
+
+    let mut seen_outputs = HashMap::new();
+    for (option, file) in outputs {
+        if let Some(first) = seen_outputs.insert(output_key(&file), option) {
+            return Err(UsageError::SameOutputPath {
+                first: first.to_owned(),
+                second: option.to_owned(),
+                path: file.to_string_lossy().into_owned(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Identifies the file an output path names: `out` and `./out` are the same file, and so are two
+/// paths through a link. Only Windows paths are case insensitive.
+fn output_key(file: &Path) -> String {
+    // the file usually doesn't exist yet, but its directory does
+    let resolved = file.canonicalize().or_else(|_| {
+        let dir = file
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        match file.file_name() {
+            Some(name) => dir.canonicalize().map(|dir| dir.join(name)),
+            None => std::path::absolute(file),
+        }
+    });
+    let key = resolved
+        .unwrap_or_else(|_| file.to_owned())
+        .to_string_lossy()
+        .into_owned();
+    if cfg!(windows) {
+        key.to_lowercase()
+    } else {
+        key
+    }
+}
+
+/// Like [`parse_args`], but with `env` to look up environment variables.
+///
+/// `FXC2_ENTRY` provides the entry point only when no `/E` is given, an explicit `/E` always wins.
+pub fn parse_args_with_env(
+    args: &[String],
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<ParsedOptions, UsageError> {
+    let mut args = args.iter().collect::<VecDeque<_>>();
+
+    let mut n_model = String::new();
+    let mut n_entry_point = CString::default();
+    let mut n_variable_name = String::new();
+    let mut n_output_file = String::new();
+    let mut n_object_file = None;
+    let mut n_assembly_file = None;
+    let mut n_set_private_file = None;
+    let mut n_get_private_file = None;
+    let mut n_set_root_signature_file = None;
+    let mut n_extract_root_signature_file = None;
+    let mut n_verify_root_signature_file = None;
+    let mut n_defines = Vec::new();
+    let mut n_flags1 = 0;
+    // these share bits in flags1, so they're kept apart until all arguments are parsed
+    let mut n_optimization_level = D3DCOMPILE_OPTIMIZATION_LEVEL1;
+    let mut n_matrix_packing = 0;
+    let mut n_output_include_details = false;
+    let mut n_echo_fxc = false;
+    let mut n_signature_file = None;
+    let mut n_with_output_signature = false;
+    let mut n_minimal = false;
+    let mut n_include_extensions = Vec::new();
+    let mut n_reflect_json_file = None;
+    let mut n_strict_defines = false;
+    let mut n_expected_bindings = Vec::new();
+    let mut n_hexdump = None;
+    let mut n_emit_loader = false;
+    let mut n_secondary_data_flags = 0;
+    let mut n_secondary_data_file = None;
+    let mut n_why_failed = false;
+    let mut n_preprocess_file = None;
+    let mut n_strip_line_directives = false;
+    let mut n_dump_bin = false;
+    let mut n_compress = false;
+    let mut n_decompress = false;
+    let mut n_expected_profile = None;
+    let mut n_compact = false;
+    let mut n_strip_root_signature = false;
+    let mut n_strip_flags = 0;
+    let mut n_language = OutputLanguage::C;
+    let mut n_header_parts = 1;
+    let mut n_cbuffer_budget = None;
+    let mut n_cbuffer_budget_error = false;
+    let mut n_sort_defines = false;
+    let mut n_descriptor_map_file = None;
+    let mut n_include_dirs = Vec::new();
+    let mut n_source_relative_includes = true;
+    let mut n_clipboard = false;
+    let mut n_entry_pattern = None;
+    let mut n_summary = false;
+    let mut n_forbidden = Vec::new();
+    let mut n_meta_file = None;
+    let mut n_meta_timestamp = false;
+    let mut n_show_hash = false;
+    let mut n_compare_precision = None;
+    let mut n_strict = false;
+    let mut n_dep_file = None;
+    let mut n_dep_relative_to = None;
+    let mut n_rc_file = None;
+    let mut n_list_profiles = false;
+    let mut n_note = None;
+    let mut n_show_note = false;
+    let mut n_threadgroup = false;
+    let mut n_byte_width = 4;
+    let mut n_indent = String::new();
+    let mut n_check = false;
+    let mut n_max_bytes = None;
+    let mut n_infer_from_output = false;
+    let mut n_trace = false;
+    let mut n_warnings_to = WarningStream::Stderr;
+    let mut n_model_aliases = HashMap::new();
+    let mut n_dump_sections = false;
+    let mut n_length_prefixed = false;
+    let mut n_pso_desc_file = None;
+    let mut n_normalize_line_endings = false;
+    let mut n_max_instructions = None;
+    let mut n_techniques_file = None;
+    let mut n_strict_args = false;
+    let mut n_bindings_hlsl_file = None;
+    let mut n_max_warnings = None;
+    let mut n_porcelain = false;
+    let mut n_errors_blob_file = None;
+    let mut n_files_from = None;
+    let mut n_null_separated = false;
+    let mut n_profile_timings = false;
+    let mut n_embed_disassembly = false;
+    let mut n_default_prefix = DEFAULT_PREFIX.to_owned();
+    let mut n_write_if_changed = false;
+    let mut n_manifest_file = None;
+    let mut n_temps_dir = None;
+    let mut n_enum_file = None;
+    let mut n_split_debug = false;
+    let mut n_golden_file = None;
+    let mut n_update_golden = false;
+    let mut n_show_defines = false;
+    let mut n_asm_inc_file = None;
+    let mut n_asm_flavor = None;
+    let mut n_timeout = None;
+    let mut n_input_files = Vec::new();
+    // the input files given after `--`, taken as they are
+    let mut n_separated_input_files = Vec::new();
+    let mut warnings = Vec::new();
+    let mut fxc_args = vec!["fxc.exe".to_owned()];
+
+    // the first option that only affects compiling from source, /dumpbin doesn't take them
+    let mut n_compile_only = None;
+    while let Some(first) = args.pop_front() {
+        if first == "--" {
+            // everything after `--` is an input file, even if it looks like an option
+            for input_file in args.drain(..) {
+                fxc_args.extend(Opts::InputFile(input_file.clone()).to_fxc());
+                n_separated_input_files.push(input_file.clone());
+            }
+            break;
+        }
+        let second = args.front();
+        let (opt, used_second) = Opts::parse(first, second.map(|x| x.as_str()))?;
+        if used_second {
+            args.pop_front();
+        }
+        if let Some(arg) = opt.to_fxc() {
+            fxc_args.push(arg);
+        }
+        if opt.is_compile_only() && n_compile_only.is_none() {
+            n_compile_only = Some(first.to_owned());
+        }
+        match opt {
+            Opts::Model(model) => n_model = model,
+            Opts::Help => {
+                return Err(UsageError::HelpRequested);
+            }
+            Opts::AllResourcesBound => n_flags1 |= D3DCOMPILE_ALL_RESOURCES_BOUND,
+            Opts::Define(name, value) => n_defines.push((name, value)),
+            Opts::EntryPointName(entry_point) => n_entry_point = entry_point,
+            Opts::UnboundedDescriptorTables => {
+                n_flags1 |= D3DCOMPILE_ENABLE_UNBOUNDED_DESCRIPTOR_TABLES
+            }
+            Opts::OutputFile(output_file) => n_output_file = output_file,
+            Opts::ObjectFile(object_file) => n_object_file = Some(object_file),
+            Opts::AssemblyFile(assembly_file) => n_assembly_file = Some(assembly_file),
+            Opts::SetPrivate(private_file) => n_set_private_file = Some(private_file),
+            Opts::GetPrivate(private_file) => n_get_private_file = Some(private_file),
+            Opts::SetRootSignature(root_signature_file) => {
+                n_set_root_signature_file = Some(root_signature_file)
+            }
+            Opts::ExtractRootSignature(root_signature_file) => {
+                n_extract_root_signature_file = Some(root_signature_file)
+            }
+            Opts::VerifyRootSignature(root_signature_file) => {
+                n_verify_root_signature_file = Some(root_signature_file)
+            }
+            Opts::BackwardsCompatibility => n_flags1 |= D3DCOMPILE_ENABLE_BACKWARDS_COMPATIBILITY,
+            Opts::EnableStrictness => n_flags1 |= D3DCOMPILE_ENABLE_STRICTNESS,
+            Opts::AvoidFlowControl => n_flags1 |= D3DCOMPILE_AVOID_FLOW_CONTROL,
+            Opts::EnableIEEEStrictness => n_flags1 |= D3DCOMPILE_IEEE_STRICTNESS,
+            Opts::PartialPrecision => n_flags1 |= D3DCOMPILE_PARTIAL_PRECISION,
+            Opts::MatchUAVs => n_secondary_data_flags |= D3DCOMPILE_SECDATA_PRESERVE_TEMPLATE_SLOTS,
+            Opts::MergeUAVs => n_secondary_data_flags |= D3DCOMPILE_SECDATA_MERGE_UAV_SLOTS,
+            Opts::NoLogo => (), // ignored
+            Opts::DisableOptimizations => n_flags1 |= D3DCOMPILE_SKIP_OPTIMIZATION,
+            Opts::DisablePreshaders => n_flags1 |= D3DCOMPILE_NO_PRESHADER,
+            Opts::OptimizationLevel0 => n_optimization_level = D3DCOMPILE_OPTIMIZATION_LEVEL0,
+            Opts::OptimizationLevel1 => n_optimization_level = D3DCOMPILE_OPTIMIZATION_LEVEL1,
+            Opts::OptimizationLevel2 => n_optimization_level = D3DCOMPILE_OPTIMIZATION_LEVEL2,
+            Opts::OptimizationLevel3 => n_optimization_level = D3DCOMPILE_OPTIMIZATION_LEVEL3,
+            Opts::StripRootSignature => n_strip_root_signature = true,
+            Opts::StripReflect => n_strip_flags |= D3DCOMPILER_STRIP_REFLECTION_DATA.0 as u32,
+            Opts::StripDebug => n_strip_flags |= D3DCOMPILER_STRIP_DEBUG_INFO.0 as u32,
+            Opts::StripPrivate => n_strip_flags |= D3DCOMPILER_STRIP_PRIVATE_DATA.0 as u32,
+            Opts::ResourceMayAlias => n_flags1 |= D3DCOMPILE_RESOURCES_MAY_ALIAS,
+            Opts::SkipValidation => n_flags1 |= D3DCOMPILE_SKIP_VALIDATION,
+            Opts::OutputIncludeProcessDetails => n_output_include_details = true,
+            Opts::VariableName(variable_name) => n_variable_name = variable_name,
+            Opts::WarningsAsErrors => n_flags1 |= D3DCOMPILE_WARNINGS_ARE_ERRORS,
+            Opts::DebugInformation => n_flags1 |= D3DCOMPILE_DEBUG,
+            Opts::PackMatrixColumnMajor => n_matrix_packing = D3DCOMPILE_PACK_MATRIX_COLUMN_MAJOR,
+            Opts::PackMatrixRowMajor => n_matrix_packing = D3DCOMPILE_PACK_MATRIX_ROW_MAJOR,
+            Opts::InputFile(input_file) => n_input_files.push(input_file),
+            Opts::EchoFxc => n_echo_fxc = true,
+            Opts::EmitSignature(signature_file) => n_signature_file = Some(signature_file),
+            Opts::WithOutputSignature => n_with_output_signature = true,
+            Opts::Minimal => n_minimal = true,
+            Opts::IncludeExtensions(extensions) => n_include_extensions.extend(extensions),
+            Opts::ReflectJson(reflect_json_file) => n_reflect_json_file = Some(reflect_json_file),
+            Opts::StrictDefines => n_strict_defines = true,
+            Opts::ExpectBinding(binding) => n_expected_bindings.push(binding),
+            Opts::Hexdump => n_hexdump = Some(n_hexdump.flatten()),
+            Opts::HexdumpLength(length) => n_hexdump = Some(Some(length)),
+            Opts::EmitLoader => n_emit_loader = true,
+            Opts::SecondaryData(secondary_data_file) => {
+                n_secondary_data_file = Some(secondary_data_file)
+            }
+            Opts::WhyFailed => n_why_failed = true,
+            Opts::Preprocess(preprocess_file) => n_preprocess_file = Some(preprocess_file),
+            Opts::StripLineDirectives => n_strip_line_directives = true,
+            Opts::DumpBin => n_dump_bin = true,
+            Opts::Compress => n_compress = true,
+            Opts::Decompress => n_decompress = true,
+            Opts::ExpectProfile(profile) => n_expected_profile = Some(profile),
+            Opts::Compact => n_compact = true,
+            Opts::Language(language) => n_language = language,
+            Opts::SplitHeader(parts) => n_header_parts = parts,
+            Opts::CbufferBudget(budget) => n_cbuffer_budget = Some(budget),
+            Opts::CbufferBudgetError => n_cbuffer_budget_error = true,
+            Opts::SortDefines => n_sort_defines = true,
+            Opts::IncludeDir(include_dir) => n_include_dirs.push(include_dir),
+            Opts::NoSourceRelativeIncludes => n_source_relative_includes = false,
+            Opts::Clipboard => n_clipboard = true,
+            Opts::EntryPattern(pattern) => n_entry_pattern = Some(pattern),
+            Opts::Summary => n_summary = true,
+            Opts::Forbid(token) => n_forbidden.push(token),
+            Opts::Meta(meta_file) => n_meta_file = Some(meta_file),
+            Opts::MetaTimestamp => n_meta_timestamp = true,
+            Opts::ShowHash => n_show_hash = true,
+            Opts::ComparePrecision(first_file) => n_compare_precision = Some(first_file),
+            Opts::Strict => n_strict = true,
+            Opts::DepFile(dep_file) => n_dep_file = Some(dep_file),
+            Opts::DepRelativeTo(dir) => n_dep_relative_to = Some(dir),
+            Opts::EmitRc(rc_file) => n_rc_file = Some(rc_file),
+            Opts::ListProfiles => n_list_profiles = true,
+            Opts::Note(note) => n_note = Some(note),
+            Opts::ShowNote => n_show_note = true,
+            Opts::Threadgroup => n_threadgroup = true,
+            Opts::ByteWidth(width) => n_byte_width = width,
+            Opts::Indent(indent) => n_indent = indent,
+            Opts::Check => n_check = true,
+            Opts::MaxBytes(max_bytes) => n_max_bytes = Some(max_bytes),
+            Opts::InferFromOutput => n_infer_from_output = true,
+            Opts::Trace => n_trace = true,
+            Opts::WarningsTo(stream) => n_warnings_to = stream,
+            Opts::DumpSections => n_dump_sections = true,
+            Opts::LengthPrefixed => n_length_prefixed = true,
+            Opts::EmitPsoDesc(pso_desc_file) => n_pso_desc_file = Some(pso_desc_file),
+            Opts::NormalizeLineEndings => n_normalize_line_endings = true,
+            Opts::MaxInstructions(max_instructions) => n_max_instructions = Some(max_instructions),
+            Opts::Techniques(techniques_file) => n_techniques_file = Some(techniques_file),
+            Opts::StrictArgs => n_strict_args = true,
+            Opts::MaxWarnings(max_warnings) => n_max_warnings = Some(max_warnings),
+            Opts::Porcelain => n_porcelain = true,
+            Opts::ErrorsBlob(errors_blob_file) => n_errors_blob_file = Some(errors_blob_file),
+            Opts::FilesFrom(files_from) => n_files_from = Some(files_from),
+            Opts::Null => n_null_separated = true,
+            Opts::ProfileTimings => n_profile_timings = true,
+            Opts::EmbedDisassembly => n_embed_disassembly = true,
+            Opts::DefaultPrefix(default_prefix) => n_default_prefix = default_prefix,
+            Opts::WriteIfChanged => n_write_if_changed = true,
+            Opts::Manifest(manifest_file) => n_manifest_file = Some(manifest_file),
+            Opts::KeepTemps(temps_dir) => n_temps_dir = Some(temps_dir),
+            Opts::EmitEnum(enum_file) => n_enum_file = Some(enum_file),
+            Opts::SplitDebug => n_split_debug = true,
+            Opts::Golden(golden_file) => n_golden_file = Some(golden_file),
+            Opts::UpdateGolden => n_update_golden = true,
+            Opts::ShowDefines => n_show_defines = true,
+            Opts::EmitAsmInc(asm_inc_file) => n_asm_inc_file = Some(asm_inc_file),
+            Opts::AsmFlavor(asm_flavor) => n_asm_flavor = Some(asm_flavor),
+            Opts::Timeout(timeout) => n_timeout = Some(timeout),
+            Opts::EmitBindingsHlsl(bindings_hlsl_file) => {
+                n_bindings_hlsl_file = Some(bindings_hlsl_file)
+            }
+            Opts::ModelAlias(alias, model) => {
+                n_model_aliases.insert(alias, model);
+            }
+            Opts::EmitDescriptorMap(descriptor_map_file) => {
+                n_descriptor_map_file = Some(descriptor_map_file)
+            }
+            // replaced by the database's arguments in split_compile_db
+            Opts::CompileDb(_) => {
+                return Err(UsageError::InvalidArgument("--compile-db".to_owned()))
+            }
+        }
+    }
+
+    n_flags1 |= n_optimization_level | n_matrix_packing;
+
+    // a /T matching an alias is replaced by its profile, once (an alias of an alias isn't
+    // followed), and before anything looks at the profile; a later --model-alias for the same
+    // name wins
+    if let Some(model) = n_model_aliases.get(&n_model) {
+        let alias = Opts::Model(n_model.clone()).to_fxc();
+        n_model = model.clone();
+        let model = Opts::Model(n_model.clone()).to_fxc();
+        for arg in fxc_args.iter_mut() {
+            if Some(&*arg) == alias.as_ref() {
+                arg.clone_from(model.as_ref().unwrap());
+            }
+        }
+    }
+
+    if n_infer_from_output && (n_model.is_empty() || n_entry_point.is_empty()) {
+        let (model, entry_point) = infer_from_output(&n_output_file).ok_or_else(|| {
+            UsageError::InvalidArgument(format!(
+                "{}: --infer-from-output expects /Fh <name>.<profile>.<entry>.<extension>",
+                n_output_file
+            ))
+        })?;
+        if n_model.is_empty() {
+            n_model = model.to_ascii_lowercase();
+            fxc_args.extend(Opts::Model(n_model.clone()).to_fxc());
+        }
+        if n_entry_point.is_empty() {
+            n_entry_point = CString::new(entry_point)
+                .map_err(|_| UsageError::InvalidArgument(entry_point.to_owned()))?;
+            fxc_args.extend(Opts::EntryPointName(n_entry_point.clone()).to_fxc());
+        }
+    }
+
+    if n_entry_point.is_empty() {
+        if let Some(entry_point) = env("FXC2_ENTRY") {
+            n_entry_point = CString::new(entry_point.as_str())
+                .map_err(|_| UsageError::InvalidArgument(entry_point))?;
+            fxc_args.extend(Opts::EntryPointName(n_entry_point.clone()).to_fxc());
+        }
+    }
+
+    if let Some(pattern) = n_entry_pattern {
+        let entry_point = n_entry_point.to_string_lossy();
+        if !pattern.is_match(&entry_point) {
+            return Err(UsageError::EntryPointMismatch {
+                entry_point: entry_point.into_owned(),
+                pattern: pattern.as_str().to_owned(),
+            });
+        }
+    }
+
+    let mut seen_defines = HashMap::new();
+    for (name, value) in n_defines.iter() {
+        let Some(first) = seen_defines.insert(name, value) else {
+            continue;
+        };
+        if first == value {
+            continue;
+        }
+        let err = UsageError::DuplicateDefine {
+            name: name.to_string_lossy().into_owned(),
+            first: first.to_string_lossy().into_owned(),
+            second: value.to_string_lossy().into_owned(),
+        };
+        if n_strict_defines {
+            return Err(err);
+        }
+        warnings.push(err.to_string());
+    }
+
+    if n_secondary_data_flags != 0 && n_secondary_data_file.is_none() {
+        return Err(UsageError::MissingArgument("-secondary".to_owned()));
+    }
+    if n_secondary_data_flags == 0 && n_secondary_data_file.is_some() {
+        warnings.push("--secondary has no effect without /matchUAVs or /mergeUAVs".to_owned());
+    }
+
+    if n_minimal {
+        if n_flags1 & D3DCOMPILE_DEBUG != 0 {
+            return Err(UsageError::ConflictingArguments(
+                "--minimal".to_owned(),
+                "/Zi".to_owned(),
+            ));
+        }
+        if n_object_file.is_none() {
+            return Err(UsageError::MissingArgument("Fo".to_owned()));
+        }
+        // the private data part holding the note is stripped
+        if n_note.is_some() {
+            return Err(UsageError::ConflictingArguments(
+                "--minimal".to_owned(),
+                "--note".to_owned(),
+            ));
+        }
+        if n_set_private_file.is_some() {
+            return Err(UsageError::ConflictingArguments(
+                "--minimal".to_owned(),
+                "/setprivate".to_owned(),
+            ));
+        }
+    }
+
+    if n_strip_root_signature && n_set_root_signature_file.is_some() {
+        return Err(UsageError::ConflictingArguments(
+            "/Qstrip_rootsignature".to_owned(),
+            "/setrootsignature".to_owned(),
+        ));
+    }
+
+    // both are stored in the single private data part
+    if n_note.is_some() && n_set_private_file.is_some() {
+        return Err(UsageError::ConflictingArguments(
+            "--note".to_owned(),
+            "/setprivate".to_owned(),
+        ));
+    }
+
+    if n_split_debug {
+        if n_flags1 & D3DCOMPILE_DEBUG == 0 {
+            return Err(UsageError::MissingArgument("Zi".to_owned()));
+        }
+        if n_object_file.is_none() {
+            return Err(UsageError::MissingArgument("Fo".to_owned()));
+        }
+    }
+
+    if n_update_golden && n_golden_file.is_none() {
+        return Err(UsageError::MissingArgument("-golden".to_owned()));
+    }
+
+    if n_asm_flavor.is_some() && n_asm_inc_file.is_none() {
+        warnings.push("--asm-flavor has no effect without --emit-asm-inc".to_owned());
+    }
+
+    if n_strip_line_directives && n_preprocess_file.is_none() {
+        warnings.push("--strip-line-directives has no effect without /P".to_owned());
+    }
+
+    if n_dump_bin && n_preprocess_file.is_some() {
+        return Err(UsageError::ConflictingArguments(
+            "/dumpbin".to_owned(),
+            "/P".to_owned(),
+        ));
+    }
+    if let Some(compile_only) = n_compile_only
+        .as_ref()
+        .filter(|_| n_compress || n_decompress)
+    {
+        let option = if n_compress {
+            "/compress"
+        } else {
+            "/decompress"
+        };
+        return Err(UsageError::ConflictingArguments(
+            option.to_owned(),
+            compile_only.clone(),
+        ));
+    }
+    if let Some(compile_only) = n_compile_only.filter(|_| n_dump_bin) {
+        return Err(UsageError::ConflictingArguments(
+            "/dumpbin".to_owned(),
+            compile_only,
+        ));
+    }
+    if n_dump_bin && n_summary {
+        return Err(UsageError::ConflictingArguments(
+            "/dumpbin".to_owned(),
+            "--summary".to_owned(),
+        ));
+    }
+
+    // with --strict-args, a flag missing its '/' (`Tps_5_0`) is an error, not the input file
+    if n_strict_args {
+        let unexpected = n_input_files.iter().find(|input_file| {
+            !Path::new(input_file).extension().is_some_and(|extension| {
+                INPUT_EXTENSIONS
+                    .iter()
+                    .any(|known| extension.eq_ignore_ascii_case(known))
+            })
+        });
+        if let Some(unexpected) = unexpected {
+            return Err(UsageError::UnexpectedInput(unexpected.clone()));
+        }
+    }
+    n_input_files.extend(n_separated_input_files);
+    let (n_input_file, n_batch_input_files) = match n_input_files.len() {
+        0 | 1 => (n_input_files.pop().unwrap_or_default(), Vec::new()),
+        _ => (String::new(), n_input_files),
+    };
+    if n_compress && n_decompress {
+        return Err(UsageError::ConflictingArguments(
+            "/compress".to_owned(),
+            "/decompress".to_owned(),
+        ));
+    }
+    if n_compress && n_object_file.is_none() {
+        return Err(UsageError::MissingArgument("Fo".to_owned()));
+    }
+    // the compressed blob, then where each of its shaders goes
+    if n_decompress && n_batch_input_files.is_empty() {
+        return Err(UsageError::MissingArgument("output file".to_owned()));
+    }
+
+    if n_techniques_file.is_some() && !n_batch_input_files.is_empty() {
+        return Err(UsageError::ConflictingArguments(
+            "--techniques".to_owned(),
+            n_batch_input_files[1].clone(),
+        ));
+    }
+
+    if n_files_from.is_some() && n_techniques_file.is_some() {
+        return Err(UsageError::ConflictingArguments(
+            "--files-from".to_owned(),
+            "--techniques".to_owned(),
+        ));
+    }
+    if n_null_separated && n_files_from.is_none() {
+        warnings.push("--null has no effect without --files-from".to_owned());
+    }
+
+    let is_effect_file = Path::new(&n_input_file)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("fx"));
+    if is_effect_file && !n_model.is_empty() && !n_model.starts_with("fx_") {
+        warnings.push(format!(
+            "{n_input_file} looks like an effect file, but /T {n_model} isn't an effect profile; \
+             use an fx_* profile (e.g. fx_5_0) to compile the whole effect, or /E to pick one \
+             of its functions"
+        ));
+    }
+
+    if n_embed_disassembly && n_output_file.is_empty() && !n_clipboard {
+        warnings.push("--embed-disasm has no effect without /Fh".to_owned());
+    }
+
+    if n_length_prefixed && n_object_file.is_none() {
+        warnings.push("--length-prefixed has no effect without /Fo".to_owned());
+    }
+
+    if n_meta_timestamp && n_meta_file.is_none() && n_manifest_file.is_none() {
+        warnings.push("--meta-timestamp has no effect without --meta or --manifest".to_owned());
+    }
+
+    let batch =
+        n_techniques_file.is_some() || n_files_from.is_some() || !n_batch_input_files.is_empty();
+
+    // the status line describes the /Fo object or /Fh header of a single compile, and stdout gets
+    // nothing else
+    if n_porcelain {
+        let conflicting = [
+            (
+                n_warnings_to == WarningStream::Stdout,
+                "--warnings-to stdout",
+            ),
+            (n_check, "--check"),
+            (n_preprocess_file.is_some(), "/P"),
+            (n_summary, "--summary"),
+            (batch, "a batch"),
+            (n_compress, "/compress"),
+            (n_decompress, "/decompress"),
+            (n_compare_precision.is_some(), "--compare-precision"),
+            (n_list_profiles, "--list-profiles"),
+            (n_show_defines, "--show-defines"),
+            (n_hexdump.is_some() && !n_clipboard, "--hexdump"),
+            // the header goes to the clipboard instead of its file
+            (
+                n_clipboard && n_hexdump.is_none() && n_object_file.is_none(),
+                "--clipboard",
+            ),
+            (n_show_hash, "--show-hash"),
+            (n_dump_sections, "--dump-sections"),
+            (n_show_note, "--show-note"),
+            (n_threadgroup, "--threadgroup"),
+            (n_cbuffer_budget.is_some(), "--cbuffer-budget"),
+            (n_max_warnings.is_some(), "--max-warnings"),
+            (n_max_instructions.is_some(), "--max-instructions"),
+            (n_max_bytes.is_some(), "--max-bytes"),
+        ];
+        if let Some((_, option)) = conflicting.iter().find(|(given, _)| *given) {
+            return Err(UsageError::ConflictingArguments(
+                "--porcelain".to_owned(),
+                option.to_string(),
+            ));
+        }
+        if n_object_file.is_none() && n_output_file.is_empty() {
+            return Err(UsageError::MissingArgument("Fo".to_owned()));
+        }
+    }
+    if n_manifest_file.is_some() && !batch {
+        warnings.push(
+            "--manifest has no effect without a batch, --techniques, --files-from or several input files".to_owned(),
+        );
+    }
+
+    if n_enum_file.is_some() {
+        if !batch {
+            warnings.push(
+                "--emit-enum has no effect without a batch, --techniques, --files-from or several input files"
+                    .to_owned(),
+            );
+        }
+        // the table points at each shader's /Fh array
+        if n_output_file.is_empty() {
+            return Err(UsageError::MissingArgument("Fh".to_owned()));
+        }
+        if n_language == OutputLanguage::CSharp {
+            return Err(UsageError::ConflictingArguments(
+                "--emit-enum".to_owned(),
+                "--lang csharp".to_owned(),
+            ));
+        }
+        if n_header_parts > 1 {
+            return Err(UsageError::ConflictingArguments(
+                "--emit-enum".to_owned(),
+                "--split-header".to_owned(),
+            ));
+        }
+    }
+
+    if n_cbuffer_budget_error && n_cbuffer_budget.is_none() {
+        return Err(UsageError::MissingArgument("-cbuffer-budget".to_owned()));
+    }
+
+    if n_header_parts > 1 {
+        // C# initializes the fields of a partial class in no particular order
+        if n_language != OutputLanguage::C {
+            return Err(UsageError::ConflictingArguments(
+                "--split-header".to_owned(),
+                "--lang".to_owned(),
+            ));
+        }
+        if n_emit_loader {
+            return Err(UsageError::ConflictingArguments(
+                "--split-header".to_owned(),
+                "--emit-loader".to_owned(),
+            ));
+        }
+        if n_clipboard {
+            return Err(UsageError::ConflictingArguments(
+                "--split-header".to_owned(),
+                "--clipboard".to_owned(),
+            ));
+        }
+    }
+
+    if n_output_file.is_empty()
+        && n_object_file.is_none()
+        && n_assembly_file.is_none()
+        && n_get_private_file.is_none()
+        && n_extract_root_signature_file.is_none()
+        && n_verify_root_signature_file.is_none()
+        && n_preprocess_file.is_none()
+        && !n_dump_bin
+        && !n_compress
+        && !n_decompress
+        && !n_clipboard
+        && !n_summary
+        && n_compare_precision.is_none()
+        && n_rc_file.is_none()
+        && n_asm_inc_file.is_none()
+        && n_golden_file.is_none()
+        && !n_list_profiles
+        && !n_check
+    {
+        return Err(UsageError::MissingArgument("Fh".to_owned()));
+    }
+
+    // only generated sources name the shader, a /Fo object is written as is
+    let names_shader = !n_output_file.is_empty()
+        || n_clipboard
+        || n_asm_inc_file.is_some()
+        || n_rc_file.is_some()
+        || n_signature_file.is_some()
+        || n_pso_desc_file.is_some()
+        || n_enum_file.is_some();
+    if !names_shader {
+        n_variable_name.clear();
+    } else if n_variable_name.is_empty() {
+        let entry_point = n_entry_point.to_string_lossy();
+        n_variable_name = format!(
+            "{}_{entry_point}",
+            variable_prefix(&n_model, &n_default_prefix)
+        );
+    } else if C_KEYWORDS.contains(&n_variable_name.as_str()) && !n_output_file.is_empty() {
+        // the derived names always have a prefix, only a user supplied one can collide, and only
+        // in a /Fh header
+        warnings.push(format!(
+            "/Vn {n_variable_name} is a C/C++ keyword, the generated header won't compile"
+        ));
+    }
+
+    Ok(ParsedOptions {
+        model: n_model,
+        entry_point: n_entry_point,
+        variable_name: n_variable_name,
+        output_file: n_output_file,
+        object_file: n_object_file,
+        assembly_file: n_assembly_file,
+        set_private_file: n_set_private_file,
+        get_private_file: n_get_private_file,
+        set_root_signature_file: n_set_root_signature_file,
+        extract_root_signature_file: n_extract_root_signature_file,
+        verify_root_signature_file: n_verify_root_signature_file,
+        defines: n_defines,
+        input_file: n_input_file,
+        input_files: n_batch_input_files,
+        flags1: n_flags1,
+        output_include_details: n_output_include_details,
+        echo_fxc: n_echo_fxc,
+        signature_file: n_signature_file,
+        with_output_signature: n_with_output_signature,
+        minimal: n_minimal,
+        include_extensions: n_include_extensions,
+        reflect_json_file: n_reflect_json_file,
+        expected_bindings: n_expected_bindings,
+        hexdump: n_hexdump,
+        emit_loader: n_emit_loader,
+        secondary_data_flags: n_secondary_data_flags,
+        secondary_data_file: n_secondary_data_file,
+        why_failed: n_why_failed,
+        preprocess_file: n_preprocess_file,
+        strip_line_directives: n_strip_line_directives,
+        dump_bin: n_dump_bin,
+        compress: n_compress,
+        decompress: n_decompress,
+        expected_profile: n_expected_profile,
+        compact: n_compact,
+        strip_root_signature: n_strip_root_signature,
+        strip_flags: n_strip_flags,
+        language: n_language,
+        header_parts: n_header_parts,
+        cbuffer_budget: n_cbuffer_budget,
+        cbuffer_budget_error: n_cbuffer_budget_error,
+        sort_defines: n_sort_defines,
+        descriptor_map_file: n_descriptor_map_file,
+        include_dirs: n_include_dirs,
+        source_relative_includes: n_source_relative_includes,
+        clipboard: n_clipboard,
+        summary: n_summary,
+        forbidden: n_forbidden,
+        meta_file: n_meta_file,
+        meta_timestamp: n_meta_timestamp,
+        show_hash: n_show_hash,
+        compare_precision: n_compare_precision,
+        strict: n_strict,
+        dep_file: n_dep_file,
+        dep_relative_to: n_dep_relative_to,
+        rc_file: n_rc_file,
+        list_profiles: n_list_profiles,
+        note: n_note,
+        show_note: n_show_note,
+        threadgroup: n_threadgroup,
+        byte_width: n_byte_width,
+        indent: n_indent,
+        check: n_check,
+        max_bytes: n_max_bytes,
+        trace: n_trace,
+        warnings_to: n_warnings_to,
+        dump_sections: n_dump_sections,
+        length_prefixed: n_length_prefixed,
+        pso_desc_file: n_pso_desc_file,
+        normalize_line_endings: n_normalize_line_endings,
+        max_instructions: n_max_instructions,
+        techniques_file: n_techniques_file,
+        strict_args: n_strict_args,
+        bindings_hlsl_file: n_bindings_hlsl_file,
+        max_warnings: n_max_warnings,
+        porcelain: n_porcelain,
+        errors_blob_file: n_errors_blob_file,
+        files_from: n_files_from,
+        null_separated: n_null_separated,
+        profile_timings: n_profile_timings,
+        embed_disassembly: n_embed_disassembly,
+        default_prefix: n_default_prefix,
+        write_if_changed: n_write_if_changed,
+        manifest_file: n_manifest_file,
+        temps_dir: n_temps_dir,
+        enum_file: n_enum_file,
+        split_debug: n_split_debug,
+        golden_file: n_golden_file,
+        update_golden: n_update_golden,
+        show_defines: n_show_defines,
+        asm_inc_file: n_asm_inc_file,
+        asm_flavor: n_asm_flavor.unwrap_or(AsmFlavor::Nasm),
+        timeout: n_timeout,
+        warnings,
+        fxc_args,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn output_collision(list: &[&str]) -> Option<(String, String)> {
+        let opts = parse_args(&args(list)).ok().expect("the arguments parse");
+        match check_output_paths(&opts) {
+            Err(UsageError::SameOutputPath { first, second, .. }) => Some((first, second)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn same_output_path() {
+        let collision = Some(("/Fh".to_owned(), "/Fo".to_owned()));
+        let base = ["/T", "ps_5_0", "x.hlsl", "/Fh", "out.h"];
+        assert_eq!(
+            output_collision(&[&base[..], &["/Fo", "out.h"]].concat()),
+            collision
+        );
+        assert_eq!(
+            output_collision(&[&base[..], &["/Fo", "./out.h"]].concat()),
+            collision
+        );
+        assert_eq!(
+            output_collision(&[&base[..], &["/Fo", "out.cso"]].concat()),
+            None
+        );
+        let case_only = output_collision(&[&base[..], &["/Fo", "Out.h"]].concat());
+        assert_eq!(case_only.is_some(), cfg!(windows));
+    }
+
+    #[test]
+    fn same_path_as_a_derived_output() {
+        let base = ["/T", "ps_5_0", "x.hlsl", "/Fh", "out.h", "/Fo", "out.cso"];
+        assert_eq!(
+            output_collision(&[&base[..], &["/Zi", "--split-debug", "/Fc", "out.pdb"]].concat()),
+            Some(("/Fc".to_owned(), "--split-debug".to_owned()))
+        );
+        assert_eq!(
+            output_collision(&[&base[..], &["--split-header", "2", "/Fc", "out.part1.h"]].concat()),
+            Some(("/Fc".to_owned(), "--split-header".to_owned()))
+        );
+        assert_eq!(
+            output_collision(
+                &[
+                    &base[..],
+                    &["--keep-temps", "temps", "/Fc", "temps/x.hlsl.i"]
+                ]
+                .concat()
+            ),
+            Some(("/Fc".to_owned(), "--keep-temps".to_owned()))
+        );
+    }
+
+    /// Writes a response file named after the test into the temp directory, returning its path.
+    fn response_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("fxc2-{}-{name}.rsp", std::process::id()));
+        std::fs::write(&path, contents).expect("the temp directory is writable");
+        path.to_string_lossy().into_owned()
+    }
+
+    fn expand(list: &[&str]) -> Result<Vec<String>, String> {
+        expand_response_files(&args(list), |file| std::fs::read_to_string(file))
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn response_file_quoting() {
+        let rsp = response_file("quoting", "/D \"NAME=a b\"  /I\"dir with spaces\"\r\n/Zi\n");
+        assert_eq!(
+            expand(&["/T", "ps_5_0", &format!("@{rsp}"), "x.hlsl"]),
+            Ok(args(&[
+                "/T",
+                "ps_5_0",
+                "/D",
+                "NAME=a b",
+                "/Idir with spaces",
+                "/Zi",
+                "x.hlsl"
+            ]))
+        );
+    }
+
+    #[test]
+    fn nested_response_file() {
+        let inner = response_file("inner", "/DINNER /Zi");
+        let outer = response_file("outer", &format!("/DBEFORE \"@{inner}\" /DAFTER"));
+        assert_eq!(
+            expand(&[&format!("@{outer}"), "x.hlsl"]),
+            Ok(args(&["/DBEFORE", "/DINNER", "/Zi", "/DAFTER", "x.hlsl"]))
+        );
+        // after `--` nothing is expanded
+        assert_eq!(
+            expand(&["--", &format!("@{outer}")]),
+            Ok(args(&["--", &format!("@{outer}")]))
+        );
+    }
+
+    #[test]
+    fn missing_response_file() {
+        let missing = std::env::temp_dir().join("fxc2-no-such-file.rsp");
+        let missing = missing.to_string_lossy();
+        let expanded = expand_response_files(&args(&[&format!("@{missing}")]), |file| {
+            std::fs::read_to_string(file)
+        });
+        assert!(matches!(
+            expanded,
+            Err(UsageError::InvalidResponseFile(file, _)) if file == missing
+        ));
+    }
+
+    #[test]
+    fn variable_name_only_for_generated_sources() {
+        let parsed = parse_args(&args(&[
+            "/T", "ps_5_0", "/E", "main", "/Fo", "a.cso", "x.hlsl",
+        ]));
+        assert_eq!(
+            parsed.ok().map(|opts| opts.variable_name).as_deref(),
+            Some("")
+        );
+        let parsed = parse_args(&args(&[
+            "/T", "ps_5_0", "/E", "main", "/Fh", "a.h", "x.hlsl",
+        ]));
+        assert_eq!(
+            parsed.ok().map(|opts| opts.variable_name).as_deref(),
+            Some("g_main")
+        );
+    }
+
+    #[test]
+    fn trailing_option_without_value() {
+        let parsed = parse_args(&args(&["/T", "ps_5_0", "/Fh", "a.h", "x.hlsl", "/E"]));
+        assert!(matches!(parsed, Err(UsageError::MissingArgument(option)) if option == "E"));
+    }
+
+    #[test]
+    fn empty_argument() {
+        let parsed = parse_args(&args(&["/T", "ps_5_0", "/Fh", "a.h", "x.hlsl", ""]));
+        assert!(matches!(parsed, Err(UsageError::InvalidArgument(arg)) if arg.is_empty()));
+    }
+
+    #[test]
+    fn bare_slash_is_an_input() {
+        // `/` on its own is no option, so like any other non-option it names the input
+        let parsed = parse_args(&args(&["/T", "ps_5_0", "/Fh", "a.h", "/"]));
+        assert_eq!(
+            parsed.ok().map(|opts| opts.input_file).as_deref(),
+            Some("/")
+        );
+    }
+
+    #[test]
+    fn non_ascii_option() {
+        let parsed = parse_args(&args(&["/T", "ps_5_0", "/Fh", "a.h", "/é", "x.hlsl"]));
+        assert!(matches!(parsed, Err(UsageError::UnknownArgument(arg)) if arg == "é"));
+        let parsed = parse_args(&args(&["/T", "ps_5_0", "/Fh", "a.h", "é.hlsl"]));
+        assert_eq!(
+            parsed.ok().map(|opts| opts.input_file).as_deref(),
+            Some("é.hlsl")
+        );
+    }
+
+    #[test]
+    fn huge_layout_widths() {
+        for option in ["--indent", "--byte-width"] {
+            let parse = |width: &str| {
+                parse_args(&args(&[
+                    "/T", "ps_5_0", "/Fh", "a.h", option, width, "x.hlsl",
+                ]))
+            };
+            assert!(parse("16").is_ok());
+            let parsed = parse("18446744073709551615");
+            assert!(
+                matches!(parsed, Err(UsageError::InvalidArgument(arg)) if arg == "18446744073709551615")
+            );
+            assert!(matches!(parse("17"), Err(UsageError::InvalidArgument(_))));
+        }
+    }
+
+    fn macros(opts: &ParsedOptions) -> Vec<(&str, &str)> {
+        opts.defines
+            .iter()
+            .map(|(name, value)| (name.to_str().unwrap(), value.to_str().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn duplicate_define() {
+        let base = ["/T", "ps_5_0", "/Fh", "a.h", "x.hlsl"];
+        let opts = parse_args(&args(&[&base[..], &["/DFOO=1", "/DFOO=2"]].concat()))
+            .ok()
+            .unwrap();
+        assert_eq!(
+            opts.warnings,
+            ["Macro 'FOO' is defined more than once, as '1' and as '2'."]
+        );
+        assert_eq!(macros(&opts), [("FOO", "1"), ("FOO", "2")]);
+
+        let strict = parse_args(&args(
+            &[&base[..], &["/DFOO=1", "/DFOO=2", "--strict-defines"]].concat(),
+        ));
+        assert!(matches!(
+            strict,
+            Err(UsageError::DuplicateDefine { name, first, second })
+                if name == "FOO" && first == "1" && second == "2"
+        ));
+
+        // the same value twice is harmless, /DFOO is /DFOO=1
+        let opts = parse_args(&args(
+            &[&base[..], &["/DFOO", "/DFOO=1", "--strict-defines"]].concat(),
+        ))
+        .ok()
+        .unwrap();
+        assert!(opts.warnings.is_empty());
+    }
+
+    #[test]
+    fn repeated_options() {
+        let opts = parse_args(&args(&[
+            "/T", "vs_5_0", "/T", "ps_5_0", "/Fh", "a.h", "/Fh", "b.h", "/DA=1", "/DB", "/O3",
+            "/O0", "/Zpr", "/Zpc", "x.hlsl",
+        ]))
+        .ok()
+        .unwrap();
+        assert_eq!(opts.model, "ps_5_0");
+        assert_eq!(opts.output_file, "b.h");
+        assert_eq!(macros(&opts), [("A", "1"), ("B", "1")]);
+        // the later level and packing replace the earlier ones instead of adding bits to them
+        let optimization = D3DCOMPILE_OPTIMIZATION_LEVEL0
+            | D3DCOMPILE_OPTIMIZATION_LEVEL1
+            | D3DCOMPILE_OPTIMIZATION_LEVEL2
+            | D3DCOMPILE_OPTIMIZATION_LEVEL3;
+        assert_eq!(opts.flags1 & optimization, D3DCOMPILE_OPTIMIZATION_LEVEL0);
+        let packing = D3DCOMPILE_PACK_MATRIX_ROW_MAJOR | D3DCOMPILE_PACK_MATRIX_COLUMN_MAJOR;
+        assert_eq!(opts.flags1 & packing, D3DCOMPILE_PACK_MATRIX_COLUMN_MAJOR);
+    }
+
+    #[test]
+    fn strip_root_signature() {
+        let opts = parse_args(&args(&[
+            "/T",
+            "ps_5_0",
+            "/Fo",
+            "a.cso",
+            "/Qstrip_rootsignature",
+            "x.hlsl",
+        ]))
+        .ok()
+        .unwrap();
+        assert!(opts.strip_root_signature);
+        assert!(opts
+            .fxc_args
+            .iter()
+            .any(|arg| arg == "/Qstrip_rootsignature"));
+    }
+
+    #[test]
+    fn keyword_variable_name() {
+        let base = ["/T", "ps_5_0", "/Fh", "a.h", "x.hlsl"];
+        let warnings = |extra: &[&str]| {
+            parse_args(&args(&[&base[..], extra].concat()))
+                .ok()
+                .unwrap()
+                .warnings
+        };
+        assert_eq!(
+            warnings(&["/Vn", "register"]),
+            ["/Vn register is a C/C++ keyword, the generated header won't compile"]
+        );
+        assert!(warnings(&["/Vn", "registers"]).is_empty());
+        // a derived name has a prefix, even for an entry point named like a keyword
+        assert!(warnings(&["/E", "const"]).is_empty());
+    }
+
+    #[test]
+    fn strip_then_write_in_one_pass() {
+        let opts = parse_args(&args(&[
+            "/dumpbin",
+            "/Qstrip_reflect",
+            "/Qstrip_debug",
+            "/Qstrip_priv",
+            "/Fo",
+            "out.cso",
+            "in.cso",
+        ]))
+        .ok()
+        .unwrap();
+        assert!(opts.dump_bin);
+        assert_eq!(
+            opts.strip_flags,
+            (D3DCOMPILER_STRIP_REFLECTION_DATA.0
+                | D3DCOMPILER_STRIP_DEBUG_INFO.0
+                | D3DCOMPILER_STRIP_PRIVATE_DATA.0) as u32
+        );
+        assert_eq!(opts.object_file.as_deref(), Some("out.cso"));
+    }
+
+    #[test]
+    fn model_alias() {
+        let parse = |extra: &[&str]| {
+            let base = [
+                "--model-alias",
+                "pixel3=ps_3_0",
+                "/E",
+                "main",
+                "/Fh",
+                "a.h",
+                "x.hlsl",
+            ];
+            parse_args(&args(&[&base[..], extra].concat()))
+                .ok()
+                .unwrap()
+        };
+        let opts = parse(&["/T", "pixel3"]);
+        assert_eq!(opts.model, "ps_3_0");
+        // the variable name is derived from the profile, not the alias
+        assert_eq!(opts.variable_name, "g_ps30_main");
+        assert!(opts.fxc_args.iter().any(|arg| arg == "/T ps_3_0"));
+        assert!(!opts.fxc_args.iter().any(|arg| arg.contains("pixel3")));
+
+        assert_eq!(parse(&["/T", "vs_3_0"]).model, "vs_3_0");
+        // an alias of an alias isn't followed
+        assert_eq!(
+            parse(&["--model-alias", "px=pixel3", "/T", "px"]).model,
+            "pixel3"
+        );
+    }
+
+    #[test]
+    fn strict_args() {
+        // `/T` missing its slash is silently taken as the input file...
+        let opts = parse_args(&args(&["/E", "main", "/Fh", "a.h", "Tps_5_0"]));
+        assert_eq!(
+            opts.ok().map(|opts| opts.input_file).as_deref(),
+            Some("Tps_5_0")
+        );
+        // ...unless --strict-args is given
+        let opts = parse_args(&args(&[
+            "--strict-args",
+            "/E",
+            "main",
+            "/Fh",
+            "a.h",
+            "Tps_5_0",
+            "x.hlsl",
+        ]));
+        assert!(matches!(opts, Err(UsageError::UnexpectedInput(arg)) if arg == "Tps_5_0"));
+        let opts = parse_args(&args(&[
+            "--strict-args",
+            "/T",
+            "ps_5_0",
+            "/Fh",
+            "a.h",
+            "X.HLSL",
+        ]));
+        assert_eq!(
+            opts.ok().map(|opts| opts.input_file).as_deref(),
+            Some("X.HLSL")
+        );
+    }
+
+    #[test]
+    fn input_after_separator() {
+        let parse = |list: &[&str]| parse_args(&args(list)).ok().map(|opts| opts.input_file);
+        let base = ["/T", "ps_5_0", "/Fh", "a.h"];
+        assert_eq!(
+            parse(&[&base[..], &["--", "-weird.hlsl"]].concat()).as_deref(),
+            Some("-weird.hlsl")
+        );
+        assert_eq!(
+            parse(&[&base[..], &["--", "/E"]].concat()).as_deref(),
+            Some("/E")
+        );
+        // without `--` it's an option
+        assert!(parse(&[&base[..], &["-weird.hlsl"]].concat()).is_none());
+        // --strict-args takes any name after `--`
+        assert_eq!(
+            parse(&[&base[..], &["--strict-args", "--", "shader.txt"]].concat()).as_deref(),
+            Some("shader.txt")
+        );
+        // every argument after it is an input
+        let opts = parse_args(&args(&[&base[..], &["--", "-a.hlsl", "/b.hlsl"]].concat()));
+        assert_eq!(
+            opts.ok().map(|opts| opts.input_files),
+            Some(args(&["-a.hlsl", "/b.hlsl"]))
+        );
+    }
+
+    #[test]
+    fn options_from_two_response_files() {
+        let first = response_file("first", "/DFIRST /I common /T vs_5_0");
+        let second = response_file("second", "/DSECOND=2 /I \"second dir\" /T ps_5_0");
+        let expanded = expand(&[
+            "/DCMDLINE",
+            &format!("@{first}"),
+            &format!("@{second}"),
+            "/I",
+            "last",
+            "/Fh",
+            "a.h",
+            "x.hlsl",
+        ])
+        .unwrap();
+        let opts = parse_args(&expanded).ok().unwrap();
+        // additive options accumulate in order, whichever file they come from
+        assert_eq!(
+            macros(&opts),
+            [("CMDLINE", "1"), ("FIRST", "1"), ("SECOND", "2")]
+        );
+        assert_eq!(opts.include_dirs, ["common", "second dir", "last"]);
+        // the later file's /T wins
+        assert_eq!(opts.model, "ps_5_0");
+    }
+
+    #[test]
+    fn default_prefix() {
+        let parse = |extra: &[&str]| {
+            let base = ["/E", "main", "/Fh", "a.h", "x.hlsl"];
+            parse_args(&args(&[&base[..], extra].concat()))
+                .ok()
+                .unwrap()
+                .variable_name
+        };
+        assert_eq!(parse(&["/T", "cs_5_0"]), "g_main");
+        assert_eq!(
+            parse(&["/T", "cs_5_0", "--default-prefix", "shader_"]),
+            "shader_main"
+        );
+        assert_eq!(
+            parse(&["/T", "cs_5_0", "--default-prefix", "shader"]),
+            "shader_main"
+        );
+        // profiles in the table keep their own prefix, and /Vn still wins
+        assert_eq!(
+            parse(&["/T", "ps_3_0", "--default-prefix", "shader"]),
+            "g_ps30_main"
+        );
+        assert_eq!(
+            parse(&["/T", "cs_5_0", "--default-prefix", "shader", "/Vn", "kMain"]),
+            "kMain"
+        );
+        let empty = parse_args(&args(&["/T", "cs_5_0", "--default-prefix", "_", "x.hlsl"]));
+        assert!(matches!(empty, Err(UsageError::InvalidArgument(arg)) if arg == "_"));
+    }
+
+    #[test]
+    fn uppercase_profile() {
+        let parse = |model: &str| {
+            parse_args(&args(&["/T", model, "/E", "main", "/Fh", "a.h", "x.hlsl"]))
+                .ok()
+                .unwrap()
+        };
+        let opts = parse("PS_3_0");
+        assert_eq!(opts.model, "ps_3_0");
+        assert_eq!(opts.variable_name, "g_ps30_main");
+        assert!(opts.fxc_args.iter().any(|arg| arg == "/T ps_3_0"));
+        assert_eq!(parse("Vs_3_0").variable_name, "g_vs30_main");
+        assert_eq!(parse("cS_5_1").model, "cs_5_1");
+    }
+
+    #[test]
+    fn porcelain_conflicts() {
+        let parse = |extra: &[&str]| {
+            let base = ["--porcelain", "/T", "ps_5_0", "/Fo", "a.cso", "x.hlsl"];
+            parse_args(&args(&[&base[..], extra].concat()))
+        };
+        assert!(parse(&[]).is_ok());
+        assert!(parse(&["/Fh", "a.h", "--emit-rc", "a.rc"]).is_ok());
+        for extra in [
+            &["--check"][..],
+            &["/P", "a.i"],
+            &["--max-bytes", "100"],
+            &["--show-hash"],
+            &["--dump-sections"],
+            &["--show-note"],
+            &["--threadgroup"],
+            &["--cbuffer-budget", "64"],
+            &["--max-warnings", "0"],
+            &["--files-from", "list.txt"],
+            &["y.hlsl"],
+        ] {
+            let parsed = parse(extra);
+            assert!(
+                matches!(&parsed, Err(UsageError::ConflictingArguments(first, _)) if first == "--porcelain"),
+                "{extra:?}"
+            );
+        }
+        // the status line needs a file to describe
+        let parsed = parse_args(&args(&[
+            "--porcelain",
+            "/T",
+            "ps_5_0",
+            "/P",
+            "a.i",
+            "x.hlsl",
+        ]));
+        assert!(parsed.is_err());
+        let parsed = parse_args(&args(&[
+            "--porcelain",
+            "/T",
+            "ps_5_0",
+            "--golden",
+            "g.cso",
+            "x.hlsl",
+        ]));
+        assert!(matches!(parsed, Err(UsageError::MissingArgument(option)) if option == "Fo"));
+    }
+
+    #[test]
+    fn long_options_need_their_full_name() {
+        for option in ["--meta-timestamps", "--cbuffer-budget-errors", "--indent4"] {
+            let parsed = parse_args(&args(&[
+                option, "x", "/T", "ps_5_0", "/Fh", "a.h", "x.hlsl",
+            ]));
+            assert!(
+                matches!(parsed, Err(UsageError::UnknownArgument(arg)) if arg == option[1..]),
+                "{option}"
+            );
+        }
+        let parsed = parse_args(&args(&[
+            "--indent=4",
+            "/T",
+            "ps_5_0",
+            "/Fh",
+            "a.h",
+            "x.hlsl",
+        ]))
+        .ok()
+        .unwrap();
+        assert_eq!(parsed.indent, "    ");
+        let parsed = parse_args(&args(&[
+            "--indent", "4", "/T", "ps_5_0", "/Fh", "a.h", "x.hlsl",
+        ]))
+        .ok()
+        .unwrap();
+        assert_eq!(parsed.indent, "    ");
+        // fxc's own options still take their value attached
+        let parsed = parse_args(&args(&["/Tps_5_0", "/Fhout.h", "x.hlsl"]))
+            .ok()
+            .unwrap();
+        assert_eq!(parsed.output_file, "out.h");
+    }
+
+    #[test]
+    fn header_parts_bounded() {
+        let parse = |parts| {
+            parse_args(&args(&[
+                "/T",
+                "ps_5_0",
+                "/Fh",
+                "a.h",
+                "--split-header",
+                parts,
+                "x.hlsl",
+            ]))
+        };
+        assert_eq!(parse("256").ok().unwrap().header_parts, 256);
+        for parts in ["0", "257", "18446744073709551615"] {
+            assert!(
+                matches!(parse(parts), Err(UsageError::InvalidArgument(arg)) if arg == parts),
+                "{parts}"
+            );
+        }
+    }
+}
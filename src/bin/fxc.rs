@@ -3,414 +3,172 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use std::{
-    collections::VecDeque,
     env,
     ffi::{c_void, CStr, CString},
-    fmt,
     fs::File,
-    io::{Read, Write},
-    mem::MaybeUninit,
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
     process::ExitCode,
     slice,
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+use fxc2::{
+    args::{
+        check_output_paths, debug_file, defines, expand_response_files, header_part_file,
+        parse_args_with_env, pragma_args, preprocessed_temp_file, split_compile_db,
+        variable_prefix, without_input_files, AsmFlavor, CompileDbRequest, ExpectedBinding,
+        OutputLanguage, ParsedOptions, UsageError, WarningStream, PROFILES,
+    },
+    bom, compile, compile_db, depfile,
+    diagnostics::{self, Severity},
+    disasm, dxbc, hresult,
+    include::IncludeHandler,
+    meta, pragma,
+    reflect::{self, ShaderReflection, SignatureParameter},
+    rootsig, summary,
+    techniques::{self, Technique},
+    timings::{self, Phase},
+    trace,
+};
 use windows::{
-    core::PCSTR,
+    core::{w, PCSTR},
+    Win32::Foundation::{ERROR_ACCESS_DENIED, ERROR_SHARING_VIOLATION, E_INVALIDARG, HANDLE},
     Win32::Graphics::{
         Direct3D::{
             Fxc::{
-                D3DCompile2, D3DCOMPILE_ALL_RESOURCES_BOUND, D3DCOMPILE_AVOID_FLOW_CONTROL,
-                D3DCOMPILE_DEBUG, D3DCOMPILE_ENABLE_BACKWARDS_COMPATIBILITY,
-                D3DCOMPILE_ENABLE_STRICTNESS, D3DCOMPILE_ENABLE_UNBOUNDED_DESCRIPTOR_TABLES,
-                D3DCOMPILE_IEEE_STRICTNESS, D3DCOMPILE_NO_PRESHADER,
-                D3DCOMPILE_OPTIMIZATION_LEVEL0, D3DCOMPILE_OPTIMIZATION_LEVEL1,
-                D3DCOMPILE_OPTIMIZATION_LEVEL3, D3DCOMPILE_PACK_MATRIX_COLUMN_MAJOR,
-                D3DCOMPILE_PACK_MATRIX_ROW_MAJOR, D3DCOMPILE_PARTIAL_PRECISION,
-                D3DCOMPILE_RESOURCES_MAY_ALIAS, D3DCOMPILE_SKIP_OPTIMIZATION,
-                D3DCOMPILE_SKIP_VALIDATION, D3DCOMPILE_WARNINGS_ARE_ERRORS,
+                D3DCompressShaders, D3DCreateBlob, D3DDecompressShaders, D3DDisassemble,
+                D3DGetBlobPart, D3DSetBlobPart, D3DStripShader, D3DCOMPILER_STRIP_DEBUG_INFO,
+                D3DCOMPILER_STRIP_PRIVATE_DATA, D3DCOMPILER_STRIP_REFLECTION_DATA,
+                D3DCOMPILER_STRIP_TEST_BLOBS, D3D_BLOB_PART, D3D_BLOB_PDB, D3D_BLOB_PRIVATE_DATA,
+                D3D_BLOB_ROOT_SIGNATURE, D3D_COMPRESS_SHADER_KEEP_ALL_PARTS, D3D_SHADER_DATA,
             },
             ID3DBlob, ID3DInclude, D3D_SHADER_MACRO,
         },
-        Hlsl::{D3DCOMPILE_OPTIMIZATION_LEVEL2, D3D_COMPILE_STANDARD_FILE_INCLUDE},
-    },
-};
-
-struct ProfilePrefix {
-    name: &'static str,
-    prefix: &'static str,
-}
-
-static PROFILE_PREFIX_TABLE: [ProfilePrefix; 12] = [
-    ProfilePrefix {
-        name: "ps_2_0",
-        prefix: "g_ps20",
-    },
-    ProfilePrefix {
-        name: "ps_2_a",
-        prefix: "g_ps21",
-    },
-    ProfilePrefix {
-        name: "ps_2_b",
-        prefix: "g_ps21",
-    },
-    ProfilePrefix {
-        name: "ps_2_sw",
-        prefix: "g_ps2ff",
-    },
-    ProfilePrefix {
-        name: "ps_3_0",
-        prefix: "g_ps30",
-    },
-    ProfilePrefix {
-        name: "ps_3_sw",
-        prefix: "g_ps3ff",
-    },
-    ProfilePrefix {
-        name: "vs_1_1",
-        prefix: "g_vs11",
-    },
-    ProfilePrefix {
-        name: "vs_2_0",
-        prefix: "g_vs20",
-    },
-    ProfilePrefix {
-        name: "vs_2_a",
-        prefix: "g_vs21",
-    },
-    ProfilePrefix {
-        name: "vs_2_sw",
-        prefix: "g_vs2ff",
+        Hlsl::D3D_COMPILE_STANDARD_FILE_INCLUDE,
     },
-    ProfilePrefix {
-        name: "vs_3_0",
-        prefix: "g_vs30",
+    Win32::System::{
+        DataExchange::{CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData},
+        LibraryLoader::{GetModuleFileNameW, GetModuleHandleW},
+        Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE},
     },
-    ProfilePrefix {
-        name: "vs_3_sw",
-        prefix: "g_vs3ff",
-    },
-];
-
-enum UsageError {
-    HelpRequested,
-    UnknownArgument(String),
-    MissingArgument(String),
-    TooManyArguments,
-}
-
-impl fmt::Display for UsageError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            UsageError::HelpRequested => write!(f, "Check https://learn.microsoft.com/en-us/windows/win32/direct3dtools/dx-graphics-tools-fxc-syntax for usage information."),
-            UsageError::UnknownArgument(arg) => {
-                writeln!(f, "Unknown argument: '{arg}'")?;
-                writeln!(f, "This isn't a sign of disaster, odds are it will be very easy to add support for this argument.")?;
-                writeln!(f, "Review the meaning of the argument in the real fxc program, and then add it into fxc2.")
-            }
-            UsageError::MissingArgument(arg) => {
-                writeln!(f, "Missing argument for: '{arg}'")?;
-                writeln!(f, "We expected to receive this, and it's likely things will nmot work correctly without it.")?;
-                writeln!(f, "Review fxc2 and make sure things will work.")
-            }
-            UsageError::TooManyArguments => write!(f, "You specified multiple input files. We did not expect to receive this, and aren't prepared to handle multiple input files. You'll have to edit the source to behave the way you want."),
-        }
-    }
-}
-
-impl From<UsageError> for ExitCode {
-    fn from(err: UsageError) -> ExitCode {
-        eprintln!("{err}");
-        ExitCode::FAILURE
-    }
-}
-
-enum Opts {
-    /// (T), Required
-    Model(String),
-    /// (?, help), Optional
-    Help,
-    /// (all_resources_bound), Optional
-    AllResourcesBound,
-    /// (D), Optional
-    Define(CString, CString),
-    /// (E), Required
-    EntryPointName(CString),
-    /// (enable_unbounded_descriptor_tables), Optional
-    UnboundedDescriptorTables,
-    /// (Fh), Required
-    OutputFile(String),
-    /// (Gec), Optional
-    BackwardsCompatibility,
-    /// (Ges), Optional
-    EnableStrictness,
-    /// (Gfa), Optional
-    AvoidFlowControl,
-    /// (Gis), Optional
-    EnableIEEEStrictness,
-    /// (Gpp), Optional
-    PartialPrecision,
-
-    // Don't know how to handle includes yet
-    /// (nologo), Optional
-    NoLogo,
-    /// (Od), Optional
-    DisableOptimizations,
-    /// (Op), Optional
-    DisablePreshaders,
-    /// (O0), Optional
-    OptimizationLevel0,
-    /// (O1), Optional
-    OptimizationLevel1,
-    /// (O2), Optional
-    OptimizationLevel2,
-    /// (O3), Optional
-    OptimizationLevel3,
-    /// (res_may_alias), Optional
-    ResourceMayAlias,
-    /// (Vd), Optional
-    SkipValidation,
-    /// (Vi), Optional
-    OutputIncludeProcessDetails,
-    /// (Vn), Optional
-    VariableName(String),
-    /// (WX), Optional
-    WarningsAsErrors,
-    /// (Zi), Optional
-    DebugInformation,
-    /// (Zpc), Optional
-    PackMatrixColumnMajor,
-    /// (Zpr)), Optional
-    PackMatrixRowMajor,
-    /// (), Input file
-    InputFile(String),
-}
-
-impl Opts {
-    /// Parses the first argument. If the argument requires an argument, and it is not already attached to the first, the next argument is used.
-    /// Returns true if the second argument was used.
-    fn parse(first: &str, second: Option<&str>) -> Result<(Opts, bool), UsageError> {
-        let first_char = first.chars().next().unwrap();
-        match first.len() {
-            0 => panic!("Empty argument"),
-            1 | _ if first_char != '-' && first_char != '/' => {
-                // not an option, assume it's the input file
-                return Ok((Opts::InputFile(first.to_owned()), false));
-            }
-            _ => {}
-        }
-        // trim the '-' or '/'
-        let mut first = &first[1..];
-        // handle no-arg options
-        match first {
-            "?" | "help" => return Ok((Opts::Help, false)),
-            "all_resources_bound" => return Ok((Opts::AllResourcesBound, false)),
-            "enable_unbounded_descriptor_tables" => {
-                return Ok((Opts::UnboundedDescriptorTables, false))
-            }
-            "Gec" => return Ok((Opts::BackwardsCompatibility, false)),
-            "Ges" => return Ok((Opts::EnableStrictness, false)),
-            "Gfa" => return Ok((Opts::AvoidFlowControl, false)),
-            "Gis" => return Ok((Opts::EnableIEEEStrictness, false)),
-            "Gpp" => return Ok((Opts::PartialPrecision, false)),
-            "nologo" => return Ok((Opts::NoLogo, false)),
-            "Od" => return Ok((Opts::DisableOptimizations, false)),
-            "Op" => return Ok((Opts::DisablePreshaders, false)),
-            "O0" => return Ok((Opts::OptimizationLevel0, false)),
-            "O1" => return Ok((Opts::OptimizationLevel1, false)),
-            "O2" => return Ok((Opts::OptimizationLevel2, false)),
-            "O3" => return Ok((Opts::OptimizationLevel3, false)),
-            "res_may_alias" => return Ok((Opts::ResourceMayAlias, false)),
-            "Vd" => return Ok((Opts::SkipValidation, false)),
-            "Vi" => return Ok((Opts::OutputIncludeProcessDetails, false)),
-            "WX" => return Ok((Opts::WarningsAsErrors, false)),
-            "Zi" => return Ok((Opts::DebugInformation, false)),
-            "Zpc" => return Ok((Opts::PackMatrixColumnMajor, false)),
-            "Zpr" => return Ok((Opts::PackMatrixRowMajor, false)),
-            _ => {}
-        }
-        // handle options with arguments.
-        // First check if the argument is attached to the option
-        let mut argument: String = String::new();
-        let mut used_second = false;
-        const ARG_PREFIX: [&str; 5] = ["T", "D", "E", "Fh", "Vn"];
-        for prefix in ARG_PREFIX.iter() {
-            if !first.starts_with(prefix) {
-                continue;
-            }
-            first = prefix;
-            let arg = &first[prefix.len()..];
-            if !arg.is_empty() {
-                argument = arg.to_owned();
-                break;
-            }
-            if let Some(second) = second {
-                argument = second.to_owned();
-                used_second = true;
-                break;
-            }
-            return Err(UsageError::MissingArgument(first.to_owned()));
-        }
-        match first {
-            "T" => Ok((Opts::Model(argument), used_second)),
-            "D" => {
-                let mut define = argument.split('=');
-                let name =
-                    CString::new(define.next().unwrap()).expect("Failed to parse define name");
-                let value = CString::new(define.next().unwrap_or("1"))
-                    .expect("Failed to parse define value");
-                Ok((Opts::Define(name, value), used_second))
-            }
-            "E" => Ok((
-                Opts::EntryPointName(
-                    CString::new(argument).expect("Failed to parse entry point name"),
-                ),
-                used_second,
-            )),
-            "Fh" => Ok((Opts::OutputFile(argument), used_second)),
-            "Vn" => Ok((Opts::VariableName(argument), used_second)),
-            _ => Err(UsageError::UnknownArgument(first.to_owned())),
-        }
-    }
-}
+};
 
+#[derive(Default)]
 struct CompileOutput {
     data: Option<ID3DBlob>,
     errors: Option<ID3DBlob>,
-}
-
-impl Default for CompileOutput {
-    fn default() -> Self {
-        Self {
-            data: None,
-            errors: None,
-        }
-    }
+    /// The files included while compiling, if they were tracked
+    includes: Vec<PathBuf>,
 }
 
 struct ParseOpt {
-    model: String,
-    entry_point: CString,
-    variable_name: String,
-    output_file: String,
-    // defines: Vec<(CString, CString)>,
+    opts: ParsedOptions,
+    // points into opts.defines
     d3d_defines: Vec<D3D_SHADER_MACRO>,
-    input_file: String,
-    flags1: u32,
+    /// The arguments the options were parsed from
+    args: Vec<String>,
 }
 
 impl ParseOpt {
     fn new() -> Result<ParseOpt, UsageError> {
-        let mut args = env::args().skip(1).collect::<VecDeque<String>>();
-
-        let mut n_model = String::new();
-        let mut n_entry_point = CString::new("").unwrap();
-        let mut n_variable_name = String::new();
-        let mut n_output_file = String::new();
-        let mut n_defines = Vec::new();
-        let mut n_d3d_defines = Vec::new();
-        let mut n_input_file = String::new();
-        let mut n_flags1 = 0;
-
-        while !args.is_empty() {
-            let first = args.pop_front().unwrap();
-            let second = args.front();
-            let (opt, used_second) = Opts::parse(&first, second.map(|x| x.as_str()))?;
-            if used_second {
-                args.pop_front();
-            }
-            match opt {
-                Opts::Model(model) => n_model = model,
-                Opts::Help => {
-                    return Err(UsageError::HelpRequested);
-                }
-                Opts::AllResourcesBound => n_flags1 |= D3DCOMPILE_ALL_RESOURCES_BOUND,
-                Opts::Define(name, value) => n_defines.push((name, value)),
-                Opts::EntryPointName(entry_point) => n_entry_point = entry_point,
-                Opts::UnboundedDescriptorTables => {
-                    n_flags1 |= D3DCOMPILE_ENABLE_UNBOUNDED_DESCRIPTOR_TABLES
-                }
-                Opts::OutputFile(output_file) => n_output_file = output_file,
-                Opts::BackwardsCompatibility => {
-                    n_flags1 |= D3DCOMPILE_ENABLE_BACKWARDS_COMPATIBILITY
-                }
-                Opts::EnableStrictness => n_flags1 |= D3DCOMPILE_ENABLE_STRICTNESS,
-                Opts::AvoidFlowControl => n_flags1 |= D3DCOMPILE_AVOID_FLOW_CONTROL,
-                Opts::EnableIEEEStrictness => n_flags1 |= D3DCOMPILE_IEEE_STRICTNESS,
-                Opts::PartialPrecision => n_flags1 |= D3DCOMPILE_PARTIAL_PRECISION,
-                Opts::NoLogo => (), // ignored
-                Opts::DisableOptimizations => n_flags1 |= D3DCOMPILE_SKIP_OPTIMIZATION,
-                Opts::DisablePreshaders => n_flags1 |= D3DCOMPILE_NO_PRESHADER,
-                Opts::OptimizationLevel0 => n_flags1 |= D3DCOMPILE_OPTIMIZATION_LEVEL0,
-                Opts::OptimizationLevel1 => n_flags1 |= D3DCOMPILE_OPTIMIZATION_LEVEL1,
-                Opts::OptimizationLevel2 => n_flags1 |= D3DCOMPILE_OPTIMIZATION_LEVEL2,
-                Opts::OptimizationLevel3 => n_flags1 |= D3DCOMPILE_OPTIMIZATION_LEVEL3,
-                Opts::ResourceMayAlias => n_flags1 |= D3DCOMPILE_RESOURCES_MAY_ALIAS,
-                Opts::SkipValidation => n_flags1 |= D3DCOMPILE_SKIP_VALIDATION,
-                Opts::OutputIncludeProcessDetails => println!(
-                    "option {first} (Output include process details) acknowledged but ignored"
-                ),
-                Opts::VariableName(variable_name) => n_variable_name = variable_name,
-                Opts::WarningsAsErrors => n_flags1 |= D3DCOMPILE_WARNINGS_ARE_ERRORS,
-                Opts::DebugInformation => n_flags1 |= D3DCOMPILE_DEBUG,
-                Opts::PackMatrixColumnMajor => n_flags1 |= D3DCOMPILE_PACK_MATRIX_COLUMN_MAJOR,
-                Opts::PackMatrixRowMajor => n_flags1 |= D3DCOMPILE_PACK_MATRIX_ROW_MAJOR,
-                Opts::InputFile(input_file) => {
-                    if !n_input_file.is_empty() {
-                        return Err(UsageError::TooManyArguments);
-                    }
-                    n_input_file = input_file;
-                }
-            }
+        let command_line = env::args().skip(1).collect::<Vec<String>>();
+        let mut args = expand_response_files(&command_line, |file| std::fs::read_to_string(file))?;
+        let mut define_origins = define_origins(&command_line);
+        if let Some(request) = split_compile_db(&args)? {
+            let db_file = request.db_file.clone();
+            args = compile_db_args(request)?;
+            // the compile database's arguments come first
+            let db_defines = defines(&args).len().saturating_sub(define_origins.len());
+            let origin = format!("compile database {db_file}");
+            define_origins.splice(0..0, std::iter::repeat_n(origin, db_defines));
+        }
+        let parse_opt = ParseOpt::from_args(args)?;
+        if parse_opt.opts.show_defines {
+            print_defines(&parse_opt.opts.defines, &define_origins);
+        }
+        Ok(parse_opt)
+    }
+    fn from_args(args: Vec<String>) -> Result<ParseOpt, UsageError> {
+        let mut opts = parse_args_with_env(&args, |name| env::var(name).ok())?;
+        let flags = source_pragma_args(&opts)?;
+        if !flags.is_empty() {
+            // the pragma's flags come first, so the command line overrides them
+            let with_flags = flags
+                .into_iter()
+                .chain(args.iter().cloned())
+                .collect::<Vec<_>>();
+            opts = parse_args_with_env(&with_flags, |name| env::var(name).ok())?;
+        }
+        check_output_paths(&opts)?;
+        if opts.trace {
+            trace::enable();
+        }
+        if opts.profile_timings {
+            timings::enable();
+        }
+
+        for warning in opts.warnings.iter() {
+            print_warning(opts.warnings_to, &format!("warning: {warning}"));
         }
+        if opts.echo_fxc {
+            eprintln!("Equivalent fxc command line:");
+            eprintln!("{}", opts.fxc_args.join(" "));
+        }
+
+        if opts.check || opts.porcelain {
+            // only the compiler's errors and warnings
+            return Ok(ParseOpt::with_options(opts, args));
+        }
+
+        eprintln!("option -T (Shader Model/Profile) with arg '{}'", opts.model);
+        eprintln!("option -E (Entry Point) with arg '{:?}'", opts.entry_point);
+        eprintln!("option -Fh (Output File) with arg {}", opts.output_file);
+        eprintln!(
+            "option -Vn (Variable Name) with arg '{}'",
+            opts.variable_name
+        );
+        if opts.sort_defines {
+            // only for display, they're passed to the compiler in the given order
+            let mut defines = opts.defines.iter().collect::<Vec<_>>();
+            defines.sort_by(|a, b| a.0.cmp(&b.0));
+            eprintln!("option -D (Macro Definition) with args {:?}", defines);
+        } else {
+            eprintln!("option -D (Macro Definition) with args {:?}", opts.defines);
+        }
+        eprintln!("Input file: {}", opts.input_file);
 
+        Ok(ParseOpt::with_options(opts, args))
+    }
+    /// The options, ready to compile with.
+    fn with_options(opts: ParsedOptions, args: Vec<String>) -> ParseOpt {
         // Default initalization and others
-        n_defines.shrink_to_fit();
-        n_d3d_defines.reserve(n_defines.len() + 1);
-        for (name, value) in n_defines.iter() {
+        // like fxc, the macros are passed first to last as given on the command line (after the
+        // compile database's), so a repeated /D and macros referring to each other behave the same
+        let mut d3d_defines = Vec::with_capacity(opts.defines.len() + 1);
+        for (name, value) in opts.defines.iter() {
             let name = PCSTR(name.as_bytes_with_nul().as_ptr());
             let value = PCSTR(value.as_bytes_with_nul().as_ptr());
-            n_d3d_defines.push(D3D_SHADER_MACRO {
+            d3d_defines.push(D3D_SHADER_MACRO {
                 Name: name,
                 Definition: value,
             });
         }
-        n_d3d_defines.push(D3D_SHADER_MACRO::default()); // null terminator
+        d3d_defines.push(D3D_SHADER_MACRO::default()); // null terminator
 
-        if n_variable_name.is_empty() {
-            let entry_point = n_entry_point.to_str().unwrap();
-            let model_name = n_model.as_str();
-            if let Some(name) = PROFILE_PREFIX_TABLE.iter().find(|i| i.name == model_name) {
-                n_variable_name = format!("{}_{entry_point}", name.prefix);
-            } else {
-                // if the model doesn't match any from our table, use g_ as the prefix
-                n_variable_name = format!("g_{entry_point}");
-            }
-        }
-
-        eprintln!("option -T (Shader Model/Profile) with arg '{n_model}'",);
-        eprintln!("option -E (Entry Point) with arg '{:?}'", n_entry_point);
-        eprintln!("option -Fh (Output File) with arg {n_output_file}");
-        eprintln!("option -Vn (Variable Name) with arg '{n_variable_name}'");
-        eprintln!("option -D (Macro Definition) with args {:?}", n_defines);
-        eprintln!("Input file: {n_input_file}");
-
-        Ok(ParseOpt {
-            model: n_model,
-            entry_point: n_entry_point,
-            variable_name: n_variable_name,
-            output_file: n_output_file,
-            // defines: n_defines,
-            d3d_defines: n_d3d_defines,
-            input_file: n_input_file,
-            flags1: n_flags1,
-        })
+        ParseOpt {
+            opts,
+            d3d_defines,
+            args,
+        }
     }
-    fn compile(self) -> (Result<(), windows::core::Error>, CompileOutput) {
-        const D3DCOMPILE_STANDARD_FILE_INCLUDE: &ID3DInclude = unsafe {
-            std::mem::transmute::<_, &ID3DInclude>(&(D3D_COMPILE_STANDARD_FILE_INCLUDE as usize))
-        };
+    /// Compiles the input, or only preprocesses it with `preprocess`.
+    fn compile(&self, preprocess: bool) -> (Result<(), windows::core::Error>, CompileOutput) {
         let input_data = {
-            let mut file = File::open(&self.input_file).expect("Failed to open input file");
+            let _timer = timings::start(Phase::Read);
+            let mut file = File::open(&self.opts.input_file).expect("Failed to open input file");
             let len = file
                 .metadata()
                 .expect("Failed to get input file metadata")
@@ -421,131 +179,2791 @@ impl ParseOpt {
                 .expect("Failed to read input file");
             data
         };
-        let file_name = CString::new(self.input_file).unwrap();
-        let model = CString::new(self.model).unwrap();
-
-        let mut data: MaybeUninit<Option<ID3DBlob>> = MaybeUninit::uninit();
-        let mut errors: MaybeUninit<Option<ID3DBlob>> = MaybeUninit::uninit();
-        let mut output: CompileOutput = Default::default();
-
-        // eprintln!("Calling D3DCompile2(");
-        // eprintln!("\t{:p},", input_data.as_ptr());
-        // eprintln!("\t{},", input_data.len());
-        // eprintln!("\t{},", file_name.to_str().unwrap());
-        // eprintln!("\t{:p},", self.d3d_defines.as_ptr());
-        // eprintln!("\tD3D_COMPILE_STANDARD_FILE_INCLUDE,");
-        // eprintln!("\t{},", self.entry_point.to_str().unwrap());
-        // eprintln!("\t{},", model.to_str().unwrap());
-        // eprintln!("\t0,");
-        // eprintln!("\t0,");
-        // eprintln!("\t0,");
-        // eprintln!("\tNULL,");
-        // eprintln!("\t0,");
-        // eprintln!("\t{:p},", data.as_mut_ptr());
-        // eprintln!("\t{:p})", errors.as_mut_ptr());
-
-        let hr = unsafe {
-            D3DCompile2(
-                input_data.as_ptr() as *const c_void,
-                input_data.len(),
-                PCSTR(file_name.as_bytes_with_nul().as_ptr() as *const u8),
-                Some(self.d3d_defines.as_ptr()),
-                D3DCOMPILE_STANDARD_FILE_INCLUDE,
-                PCSTR(self.entry_point.as_bytes_with_nul().as_ptr()),
-                PCSTR(model.as_bytes_with_nul().as_ptr()),
-                self.flags1,
-                0,
-                0,
-                None,
-                0,
-                data.as_mut_ptr(),
-                Some(errors.as_mut_ptr()),
-            )
+        if self.opts.dump_bin {
+            // the input is already compiled, pass it through as is
+            let mut output = CompileOutput::default();
+            match create_blob(&input_data) {
+                Ok(blob) => output.data = Some(blob),
+                Err(err) => return (Err(err), output),
+            }
+            return (Ok(()), output);
+        }
+        if dxbc::is_container(&input_data) {
+            let mut output = CompileOutput::default();
+            let message = format!(
+                "{} is already a compiled shader, use /dumpbin to inspect it instead of compiling it\0",
+                self.opts.input_file
+            );
+            output.errors = create_blob(message.as_bytes()).ok();
+            return (Err(E_INVALIDARG.into()), output);
+        }
+        // only the bytes handed to the compiler change, not the file
+        let input_data = compile::prepare_source(input_data);
+        let input_data = if self.opts.normalize_line_endings {
+            normalize_line_endings(&input_data)
+        } else {
+            input_data
+        };
+        let secondary_data = self
+            .opts
+            .secondary_data_file
+            .as_ref()
+            .map(|file| std::fs::read(file).expect("Failed to read secondary data file"))
+            .unwrap_or_default();
+        let file_name = CString::new(self.opts.input_file.as_str()).unwrap();
+        let model = CString::new(self.opts.model.as_str()).unwrap();
+
+        let include_handler = IncludeHandler::new(
+            Path::new(&self.opts.input_file),
+            self.opts.include_dirs.iter().map(PathBuf::from).collect(),
+            self.opts.source_relative_includes,
+            self.opts.include_extensions.clone(),
+            self.opts.output_include_details,
+        );
+        let custom_include = ID3DInclude::new(&include_handler);
+        // the standard handler knows nothing about /I, so it's only good for the simplest case
+        let include: &ID3DInclude = if self.opts.include_dirs.is_empty()
+            && self.opts.source_relative_includes
+            && self.opts.include_extensions.is_empty()
+            && !self.opts.output_include_details
+            && self.opts.dep_file.is_none()
+        {
+            D3DCOMPILE_STANDARD_FILE_INCLUDE
+        } else {
+            &custom_include
+        };
+
+        let include_name = if std::ptr::eq(include, D3DCOMPILE_STANDARD_FILE_INCLUDE) {
+            "D3D_COMPILE_STANDARD_FILE_INCLUDE"
+        } else {
+            "custom include handler"
+        };
+        let call = compile::Call {
+            source: &input_data,
+            file_name: &file_name,
+            defines: &self.d3d_defines,
+            include,
+            include_name,
+            entry_point: &self.opts.entry_point,
+            profile: &model,
+            flags1: self.opts.flags1,
+            secondary_data_flags: self.opts.secondary_data_flags,
+            secondary_data: &secondary_data,
+        };
+        let called = if preprocess {
+            call.preprocess()
+        } else {
+            call.compile()
+        };
+        let output = CompileOutput {
+            data: called.data,
+            // on success, the warnings if any
+            errors: called.errors,
+            includes: include_handler.included_files(),
+        };
+        (called.result, output)
+    }
+}
+
+/// Prints warnings, fxc2's or the compiler's, where --warnings-to says. Failures and progress
+/// notes go to stderr; reports, from the inspection options and the budget and limit checks, go
+/// to stdout.
+fn print_warning(to: WarningStream, text: &str) {
+    match to {
+        WarningStream::Stdout => println!("{text}"),
+        WarningStream::Stderr => eprintln!("{text}"),
+    }
+}
+
+/// Reports a failure on stderr: what failed, then why.
+fn print_failure(what: &str, err: impl std::fmt::Display) {
+    eprintln!("Failed to {what}:");
+    eprintln!("{err}");
+}
+
+/// Prints the messages of a failed compile. Warnings, with their continuation lines, go where
+/// --warnings-to says and everything else to stderr.
+fn print_compiler_messages(to: WarningStream, messages: &str) {
+    let mut warning = false;
+    for line in messages.lines() {
+        if let Some(diagnostic) = diagnostics::parse_line(line) {
+            warning = diagnostic.severity == Severity::Warning;
+        }
+        if warning {
+            print_warning(to, line);
+        } else {
+            eprintln!("{line}");
+        }
+    }
+}
+
+/// Turns CRLF and lone CR line endings into LF.
+fn normalize_line_endings(data: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(data.len());
+    let mut bytes = data.iter().peekable();
+    while let Some(&byte) = bytes.next() {
+        if byte != b'\r' {
+            normalized.push(byte);
+        } else if bytes.peek() != Some(&&b'\n') {
+            normalized.push(b'\n');
+        }
+    }
+    normalized
+}
+
+/// Where each `/D` of the command line comes from, in order: the command line itself or the
+/// response file it was expanded from.
+fn define_origins(command_line: &[String]) -> Vec<String> {
+    let mut origins = Vec::new();
+    // consecutive arguments given on the command line itself
+    let mut direct = Vec::new();
+    for arg in command_line.iter().take_while(|arg| *arg != "--") {
+        let Some(file) = arg.strip_prefix('@') else {
+            direct.push(arg.clone());
+            continue;
         };
-        if hr.is_err() {
-            if let Some(errors) = unsafe { errors.assume_init() } {
-                output.errors = Some(errors);
+        origins.extend(defines(&direct).iter().map(|_| "command line".to_owned()));
+        direct.clear();
+        let expanded = expand_response_files(std::slice::from_ref(arg), |file| {
+            std::fs::read_to_string(file)
+        })
+        .unwrap_or_default();
+        origins.extend(
+            defines(&expanded)
+                .iter()
+                .map(|_| format!("response file {file}")),
+        );
+    }
+    origins.extend(defines(&direct).iter().map(|_| "command line".to_owned()));
+    origins
+}
+
+/// Prints the defines as `NAME=VALUE (origin)` in the order the compiler gets them, which is the
+/// order they're resolved in: a later define of the same macro overrides an earlier one.
+fn print_defines(defines: &[(CString, CString)], origins: &[String]) {
+    for (i, (name, value)) in defines.iter().enumerate() {
+        let origin = origins.get(i).map_or("command line", String::as_str);
+        let overridden = defines[i + 1..].iter().any(|(other, _)| other == name);
+        println!(
+            "{}={} ({origin}{})",
+            name.to_string_lossy(),
+            value.to_string_lossy(),
+            if overridden { ", overridden" } else { "" }
+        );
+    }
+}
+
+/// The arguments listed by `#pragma fxc_flags` in the input source, if any. A source that can't be
+/// read has none here, compiling it reports the error.
+fn source_pragma_args(opts: &ParsedOptions) -> Result<Vec<String>, UsageError> {
+    if opts.dump_bin || opts.compress || opts.decompress || opts.input_file.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Ok(source) = std::fs::read(&opts.input_file) else {
+        return Ok(Vec::new());
+    };
+    let source = bom::transcode(source);
+    let source = String::from_utf8_lossy(&source);
+    let flags = pragma::fxc_flags(&source)
+        .map_err(|err| UsageError::InvalidPragma(opts.input_file.clone(), err))?;
+    pragma_args(&opts.input_file, &flags)
+}
+
+/// Looks up the arguments recorded for the input file and moves into their directory.
+fn compile_db_args(request: CompileDbRequest) -> Result<Vec<String>, UsageError> {
+    let invalid = |err: String| UsageError::InvalidCompileDb(request.db_file.clone(), err);
+    let db = std::fs::read_to_string(&request.db_file).map_err(|err| invalid(err.to_string()))?;
+    let db_dir = Path::new(&request.db_file)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let entry = compile_db::find(&db, db_dir, Path::new(&request.input_file))
+        .map_err(invalid)?
+        .ok_or_else(|| {
+            UsageError::NotInCompileDb(request.db_file.clone(), request.input_file.clone())
+        })?;
+    env::set_current_dir(&entry.directory).map_err(|err| invalid(err.to_string()))?;
+    eprintln!(
+        "Using the compile database arguments for {}",
+        request.input_file
+    );
+    Ok(entry
+        .arguments
+        .into_iter()
+        .chain(request.extra_args)
+        .collect())
+}
+
+/// Replaces the clipboard contents with the text.
+fn copy_to_clipboard(text: &[u8]) -> windows::core::Result<()> {
+    // from Win32_System_Ole, not worth the feature for one constant
+    const CF_TEXT: u32 = 1;
+
+    unsafe {
+        OpenClipboard(None)?;
+        let result = EmptyClipboard().and_then(|()| {
+            let memory = GlobalAlloc(GMEM_MOVEABLE, text.len() + 1)?;
+            let data = GlobalLock(memory) as *mut u8;
+            std::ptr::copy_nonoverlapping(text.as_ptr(), data, text.len());
+            *data.add(text.len()) = 0;
+            // reports an "error" once the memory is unlocked
+            let _ = GlobalUnlock(memory);
+            // the clipboard owns the memory from here on
+            SetClipboardData(CF_TEXT, HANDLE(memory.0 as isize))?;
+            Ok(())
+        });
+        CloseClipboard()?;
+        result
+    }
+}
+
+fn create_blob(data: &[u8]) -> windows::core::Result<ID3DBlob> {
+    unsafe {
+        let blob = D3DCreateBlob(data.len());
+        trace::call("D3DCreateBlob", format_args!("{}", data.len()), &blob);
+        let blob = blob?;
+        std::ptr::copy_nonoverlapping(
+            data.as_ptr(),
+            blob.GetBufferPointer() as *mut u8,
+            data.len(),
+        );
+        Ok(blob)
+    }
+}
+
+const D3DCOMPILE_STANDARD_FILE_INCLUDE: &ID3DInclude = unsafe {
+    std::mem::transmute::<_, &ID3DInclude>(&(D3D_COMPILE_STANDARD_FILE_INCLUDE as usize))
+};
+
+fn blob_bytes(blob: &ID3DBlob) -> &[u8] {
+    unsafe {
+        let data = blob.GetBufferPointer() as *const u8;
+        let len = blob.GetBufferSize();
+        slice::from_raw_parts(data, len)
+    }
+}
+
+/// Removes `#line` directives and trailing whitespace, ending the text with a single newline.
+fn strip_line_directives(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    for line in text.lines() {
+        if line.trim_start().starts_with("#line") {
+            continue;
+        }
+        stripped.push_str(line.trim_end());
+        stripped.push('\n');
+    }
+    stripped.truncate(stripped.trim_end().len());
+    stripped.push('\n');
+    stripped
+}
+
+/// The text of the /P output, with `#line` directives removed if `strip` is set.
+fn preprocessed_text(data: &[u8], strip: bool) -> String {
+    let text = String::from_utf8_lossy(data);
+    let text = text.trim_end_matches('\0');
+    if strip {
+        strip_line_directives(text)
+    } else {
+        text.to_owned()
+    }
+}
+
+fn write_preprocessed(
+    output: &ID3DBlob,
+    preprocess_file: &str,
+    strip: bool,
+) -> Result<(), std::io::Error> {
+    let text = preprocessed_text(blob_bytes(output), strip);
+    write_file(preprocess_file, text.as_bytes(), false)?;
+    Ok(())
+}
+
+/// Preprocesses the input on its own and writes the result to `<temps_dir>/<input file name>.i`,
+/// for --keep-temps. This is a second pass over the input and its includes besides the compile.
+fn keep_preprocessed(args: &ParseOpt, temps_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let (result, output) = args.compile(true);
+    result?;
+    std::fs::create_dir_all(temps_dir)?;
+    let path = preprocessed_temp_file(temps_dir, &args.opts.input_file);
+    if let Some(data) = &output.data {
+        write_preprocessed(data, &path.to_string_lossy(), false)?;
+        eprintln!("Kept the preprocessed source in {}", path.display());
+    }
+    Ok(())
+}
+
+fn print_summary(summary: &summary::Summary) {
+    for (kind, names) in [
+        ("Functions", &summary.functions),
+        ("Constant buffers", &summary.cbuffers),
+        ("Textures", &summary.textures),
+        ("Samplers", &summary.samplers),
+    ] {
+        println!("{kind} ({}): {}", names.len(), names.join(", "));
+    }
+}
+
+/// Creates the file, retrying for a little while if another process (typically an antivirus)
+/// briefly holds it open.
+fn create_output_file(path: impl AsRef<Path>) -> Result<File, std::io::Error> {
+    const ATTEMPTS: u32 = 5;
+    let is_locked = |err: &std::io::Error| {
+        err.raw_os_error() == Some(ERROR_SHARING_VIOLATION.0 as i32)
+            || err.raw_os_error() == Some(ERROR_ACCESS_DENIED.0 as i32)
+    };
+    let mut delay = Duration::from_millis(10);
+    let mut attempt = 1;
+    loop {
+        match File::create(&path) {
+            Err(err) if is_locked(&err) && attempt < ATTEMPTS => {
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
             }
-            return (hr, output);
+            Err(err) if is_locked(&err) => {
+                let message = format!(
+                    "{} is still in use after {ATTEMPTS} attempts: {err}",
+                    path.as_ref().display()
+                );
+                return Err(std::io::Error::new(err.kind(), message));
+            }
+            result => return result,
         }
+    }
+}
 
-        output.data = Some(unsafe { data.assume_init() }.unwrap());
-        (hr, output)
+/// Writes the contents to the file, unless `if_changed` is set and the file already holds exactly
+/// them, which keeps its modification time for incremental builds. Returns whether it was written.
+fn write_file(
+    path: impl AsRef<Path>,
+    contents: &[u8],
+    if_changed: bool,
+) -> Result<bool, std::io::Error> {
+    if if_changed && std::fs::read(&path).is_ok_and(|existing| existing == contents) {
+        return Ok(false);
     }
+    create_output_file(path)?.write_all(contents)?;
+    Ok(true)
+}
+
+/// How the bytes of a header are laid out.
+struct ArrayStyle<'a> {
+    compact: bool,
+    language: OutputLanguage,
+    /// The minimum width of every byte, unused when compact
+    byte_width: usize,
+    /// Written at the start of every row of bytes
+    indent: &'a str,
+}
+
+/// Splits `data` into exactly `parts` slices whose lengths differ by at most one, the longer ones
+/// first.
+fn split_evenly(data: &[u8], parts: usize) -> impl Iterator<Item = &[u8]> {
+    let (len, longer) = (data.len() / parts, data.len() % parts);
+    (0..parts).map(move |part| {
+        let start = part * len + part.min(longer);
+        &data[start..start + len + usize::from(part < longer)]
+    })
 }
 
 fn write_output(
-    output: ID3DBlob,
-    output_file: String,
-    variable_name: String,
+    output: &ID3DBlob,
+    output_file: &str,
+    variable_name: &str,
+    trailer: Option<&str>,
+    style: &ArrayStyle,
+    parts: usize,
+    if_changed: bool,
 ) -> Result<(), std::io::Error> {
-    let data: &[u8] = unsafe {
-        let out_string = output.GetBufferPointer() as *const u8;
-        let len = output.GetBufferSize();
-        slice::from_raw_parts(out_string, len)
-    };
+    let data = blob_bytes(output);
 
-    let mut file = File::create(output_file.clone()).expect("Failed to create output file");
+    let mut file = Vec::new();
 
-    write!(file, "const BYTE {variable_name}[] =\n{{\n")?;
-    for (i, byte) in data.iter().enumerate() {
-        let byte = *byte as i8;
+    if parts > 1 {
+        // <name>.partN.<ext> holds <variable_name>_partN, the main file includes them in order
+        let mut names = Vec::new();
+        if parts > data.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("can't split {} bytes into {parts} parts", data.len()),
+            ));
+        }
+        for (i, chunk) in split_evenly(data, parts).enumerate() {
+            let part_path = header_part_file(output_file, i);
+            let part_name = format!("{variable_name}_part{i}");
+            let mut part = Vec::new();
+            write_array(&mut part, chunk, &part_name, style)?;
+            writeln!(part)?;
+            write_file(&part_path, &part, if_changed)?;
+            let part_file = part_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            names.push((part_file, part_name));
+        }
+        writeln!(
+            file,
+            "// {variable_name} is split into {} parts, concatenate them in order to get the shader",
+            names.len()
+        )?;
+        for (part_file, _) in names.iter() {
+            writeln!(file, "#include \"{part_file}\"")?;
+        }
+        let pointers = names
+            .iter()
+            .map(|(_, name)| name.as_str())
+            .collect::<Vec<_>>();
+        let sizes = names
+            .iter()
+            .map(|(_, name)| format!("sizeof({name})"))
+            .collect::<Vec<_>>();
+        writeln!(
+            file,
+            "\nconst BYTE* const {variable_name}_parts[] = {{ {} }};",
+            pointers.join(", ")
+        )?;
         write!(
             file,
-            "{:4}{}",
-            byte,
+            "const SIZE_T {variable_name}_part_sizes[] = {{ {} }};",
+            sizes.join(", ")
+        )?;
+    } else {
+        write_array(&mut file, data, variable_name, style)?;
+    }
+    if let Some(trailer) = trailer {
+        write!(file, "\n\n{trailer}")?;
+    }
+
+    if write_file(output_file, &file, if_changed)? {
+        eprintln!(
+            "Wrote {} bytes of shader output to {}",
+            data.len(),
+            output_file
+        );
+    } else {
+        eprintln!("Shader output in {output_file} is unchanged");
+    }
+    Ok(())
+}
+
+fn write_array(
+    file: &mut impl Write,
+    data: &[u8],
+    variable_name: &str,
+    style: &ArrayStyle,
+) -> Result<(), std::io::Error> {
+    // lines stay well below the 4095 characters every C compiler has to accept
+    const COMPACT_LINE_WIDTH: usize = 120;
+
+    match style.language {
+        OutputLanguage::C => write!(file, "const BYTE {variable_name}[] =\n{{\n")?,
+        OutputLanguage::StdArray => write!(
+            file,
+            "#include <array>\n#include <cstdint>\n\ninline constexpr std::array<std::uint8_t, {}> {variable_name} =\n{{\n",
+            data.len()
+        )?,
+        OutputLanguage::CSharp => write!(
+            file,
+            "public static partial class Shaders\n{{\npublic static readonly byte[] {variable_name} = new byte[]\n{{\n"
+        )?,
+    }
+    let indent = style.indent;
+    if style.compact {
+        let mut line = String::from(indent);
+        for (i, byte) in data.iter().enumerate() {
+            let byte = byte.to_string();
+            if line.len() > indent.len() && line.len() + byte.len() + 1 > COMPACT_LINE_WIDTH {
+                writeln!(file, "{line}")?;
+                line.clear();
+                line.push_str(indent);
+            }
+            line.push_str(&byte);
             if i != data.len() - 1 {
-                ","
-            } else if i % 6 == 5 {
-                "\n"
-            } else {
-                ""
+                line.push(',');
             }
-        )?;
+        }
+        write!(file, "{line}")?;
+    } else {
+        // like fxc: unsigned bytes, 6 to a line
+        write!(file, "{indent}")?;
+        for (i, byte) in data.iter().enumerate() {
+            if i != 0 && i % 6 == 0 {
+                write!(file, "\n{indent}")?;
+            }
+            write!(file, "{byte:width$}", width = style.byte_width)?;
+            if i != data.len() - 1 {
+                write!(file, ",")?;
+            }
+        }
     }
     write!(file, "\n}};")?;
+    if style.language == OutputLanguage::CSharp {
+        write!(file, "\n}}")?;
+    }
+    Ok(())
+}
 
-    eprintln!(
-        "Wrote {} bytes of shader output to {}",
-        data.len(),
-        output_file
+/// Generates an inline C++ function creating the D3D11 shader object for the profile's stage.
+/// This is a convenience for D3D11 users, anything fancier is left to the engine.
+fn loader_snippet(model: &str, variable_name: &str) -> Result<String, String> {
+    let kind = match model.split('_').next() {
+        Some("vs") => "Vertex",
+        Some("ps") => "Pixel",
+        Some("gs") => "Geometry",
+        Some("hs") => "Hull",
+        Some("ds") => "Domain",
+        Some("cs") => "Compute",
+        _ => return Err(format!("no D3D11 shader stage matches profile '{model}'")),
+    };
+    if !model.contains("_4_") && !model.contains("_5_") {
+        return Err(format!("profile '{model}' can't be loaded by D3D11"));
+    }
+    Ok(format!(
+        "inline HRESULT Create_{variable_name}(ID3D11Device *device, ID3D11{kind}Shader **shader)
+{{
+    return device->Create{kind}Shader({variable_name}, sizeof({variable_name}), nullptr, shader);
+}}
+"
+    ))
+}
+
+fn threads_declaration(threads: [u32; 3], variable_name: &str, language: OutputLanguage) -> String {
+    let [x, y, z] = threads;
+    match language {
+        OutputLanguage::C => {
+            format!("const UINT {variable_name}_threads[3] = {{ {x}, {y}, {z} }};\n")
+        }
+        OutputLanguage::StdArray => format!(
+            "inline constexpr std::array<std::uint32_t, 3> {variable_name}_threads = {{ {x}, {y}, {z} }};\n"
+        ),
+        OutputLanguage::CSharp => format!(
+            "public static partial class Shaders\n{{\npublic static readonly uint[] {variable_name}_threads = {{ {x}, {y}, {z} }};\n}}\n"
+        ),
+    }
+}
+
+/// Removes the given parts from a compiled shader and fixes up the container checksum.
+fn strip_shader(data: &[u8], strip_flags: i32) -> windows::core::Result<Vec<u8>> {
+    let stripped = unsafe {
+        D3DStripShader(
+            data.as_ptr() as *const c_void,
+            data.len(),
+            strip_flags as u32,
+        )
+    };
+    trace::call(
+        "D3DStripShader",
+        format_args!("{} bytes, flags 0x{:X}", data.len(), strip_flags),
+        &stripped,
     );
-    Ok(())
+    let stripped = stripped?;
+    let mut stripped = blob_bytes(&stripped).to_vec();
+    dxbc::update_checksum(&mut stripped);
+    Ok(stripped)
 }
 
-fn main() -> ExitCode {
-    // ====================================================================================
-    // Shader Compilation
+/// Returns a part of the shader, `part_name` naming it in the trace.
+fn get_blob_part(
+    data: &[u8],
+    part: D3D_BLOB_PART,
+    part_name: &str,
+) -> windows::core::Result<ID3DBlob> {
+    let blob = unsafe { D3DGetBlobPart(data.as_ptr() as *const c_void, data.len(), part, 0) };
+    trace::call(
+        "D3DGetBlobPart",
+        format_args!("{} bytes, {part_name}", data.len()),
+        &blob,
+    );
+    blob
+}
 
-    let args = match ParseOpt::new() {
-        Ok(args) => args,
-        Err(err) => return err.into(),
+/// Returns the shader with a part replaced by `part_data`, `part_name` naming it in the trace.
+fn set_blob_part(
+    data: &[u8],
+    part: D3D_BLOB_PART,
+    part_name: &str,
+    part_data: &[u8],
+) -> windows::core::Result<ID3DBlob> {
+    let blob = unsafe {
+        D3DSetBlobPart(
+            data.as_ptr() as *const c_void,
+            data.len(),
+            part,
+            0,
+            part_data.as_ptr() as *const c_void,
+            part_data.len(),
+        )
     };
-    let output_file = args.output_file.clone();
-    let variable_name = args.variable_name.clone();
-    let output = match args.compile() {
-        (Ok(()), output) => output,
-        (Err(err), output) => {
-            eprintln!("Got an error while compiling:");
-            eprintln!("{}", err);
-            if let Some(errors) = output.errors {
-                let error = unsafe { CStr::from_ptr(errors.GetBufferPointer() as *const i8) };
-                eprintln!("{}", error.to_string_lossy());
-            } else {
-                eprintln!("No error message from the function");
+    trace::call(
+        "D3DSetBlobPart",
+        format_args!(
+            "{} bytes, {part_name}, {} bytes of part data",
+            data.len(),
+            part_data.len()
+        ),
+        &blob,
+    );
+    blob
+}
+
+/// Checks that the root signature in the file covers every resource the shader binds, reporting
+/// the ones it doesn't. Returns false if there are any.
+fn verify_root_signature(
+    data: &[u8],
+    root_signature_file: &str,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let registers = rootsig::registers(&std::fs::read(root_signature_file)?)?;
+    let reflection = ShaderReflection::new(data)?;
+    let profile = reflection.profile();
+    let stage = profile.split('_').next().unwrap_or_default();
+    let bindings = reflection.resource_bindings()?;
+    let unbound = rootsig::unbound(&registers, stage, &bindings);
+    for binding in unbound.iter() {
+        eprintln!(
+            "error: {} ({}{}, space{}) isn't in the root signature {root_signature_file}",
+            binding.name,
+            binding.register_class(),
+            binding.bind_point,
+            binding.space
+        );
+    }
+    Ok(unbound.is_empty())
+}
+
+/// Writes the debug information of a compiled shader to a `.pdb` named after the object file, like
+/// fxc's /Fd, and returns the shader without it.
+fn split_debug(
+    data: &[u8],
+    object_file: &str,
+    if_changed: bool,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let pdb = get_blob_part(data, D3D_BLOB_PDB, "D3D_BLOB_PDB")
+        .map_err(|_| "the shader has no debug information, compile it with /Zi")?;
+    let pdb_file = debug_file(object_file);
+    if write_file(&pdb_file, blob_bytes(&pdb), if_changed)? {
+        eprintln!("Wrote debug information to {}", pdb_file.display());
+    } else {
+        eprintln!("Debug information in {} is unchanged", pdb_file.display());
+    }
+    // strip_shader fixes up the container checksum
+    Ok(strip_shader(data, D3DCOMPILER_STRIP_DEBUG_INFO.0)?)
+}
+
+/// Writes the data in the classic offset/hex/ASCII layout, 16 bytes per line.
+fn write_hexdump(out: &mut impl Write, data: &[u8]) -> Result<(), std::io::Error> {
+    for (i, line) in data.chunks(16).enumerate() {
+        write!(out, "{:08x} ", i * 16)?;
+        for column in 0..16 {
+            if column == 8 {
+                write!(out, " ")?;
+            }
+            match line.get(column) {
+                Some(byte) => write!(out, " {byte:02x}")?,
+                None => write!(out, "   ")?,
             }
-            return ExitCode::FAILURE;
         }
-    };
+        let ascii = line
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect::<String>();
+        writeln!(out, "  |{ascii}|")?;
+    }
+    writeln!(out, "{:08x}", data.len())
+}
+
+/// With `length_prefixed`, the raw container is preceded by its size as a little endian u32.
+fn write_object(
+    data: &[u8],
+    object_file: &str,
+    length_prefixed: bool,
+    if_changed: bool,
+) -> Result<(), std::io::Error> {
+    let mut contents = Vec::with_capacity(data.len() + 4);
+    if length_prefixed {
+        contents.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    }
+    contents.extend_from_slice(data);
+    if write_file(object_file, &contents, if_changed)? {
+        eprintln!(
+            "Wrote {} bytes of shader object to {}",
+            data.len(),
+            object_file
+        );
+    } else {
+        eprintln!("Shader object in {object_file} is unchanged");
+    }
+    Ok(())
+}
 
-    let output = output.data.unwrap();
+/// Writes the shader as data declarations for an assembler, to `%include` (NASM) or `include`
+/// (MASM) in a data section.
+///
+/// The bytes are labeled with the variable name, and `<variable_name>_size` is their count.
+fn write_asm_inc(
+    data: &[u8],
+    asm_inc_file: &str,
+    variable_name: &str,
+    flavor: AsmFlavor,
+    if_changed: bool,
+) -> Result<(), std::io::Error> {
+    let mut file = Vec::new();
+    writeln!(
+        file,
+        "; {variable_name}: {} bytes of compiled shader",
+        data.len()
+    )?;
+    if flavor == AsmFlavor::Nasm {
+        writeln!(file, "{variable_name}:")?;
+    }
+    for (i, line) in data.chunks(16).enumerate() {
+        let bytes = line
+            .iter()
+            .map(|byte| match flavor {
+                AsmFlavor::Nasm => format!("0x{byte:02x}"),
+                AsmFlavor::Masm => format!("0{byte:02X}h"),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        match flavor {
+            AsmFlavor::Nasm => writeln!(file, "    db {bytes}")?,
+            // only the first declaration carries the label, the rest follow it
+            AsmFlavor::Masm => {
+                let label = if i == 0 { variable_name } else { "" };
+                writeln!(
+                    file,
+                    "{label:<width$} BYTE {bytes}",
+                    width = variable_name.len()
+                )?
+            }
+        }
+    }
+    match flavor {
+        AsmFlavor::Nasm => writeln!(file, "{variable_name}_size equ $ - {variable_name}")?,
+        AsmFlavor::Masm => writeln!(file, "{variable_name}_size EQU $ - {variable_name}")?,
+    }
 
-    match write_output(output, output_file, variable_name) {
-        Ok(()) => ExitCode::SUCCESS,
-        Err(err) => {
-            eprintln!("Failed to write output file:");
-            eprintln!("{}", err);
-            ExitCode::FAILURE
+    if write_file(asm_inc_file, &file, if_changed)? {
+        eprintln!("Wrote {variable_name} to {asm_inc_file}");
+    } else {
+        eprintln!("{asm_inc_file} is unchanged");
+    }
+    Ok(())
+}
+
+/// Writes the shader as an `RCDATA` entry of a resource script.
+///
+/// The resource is named after the variable name in upper case (which is how rc stores names
+/// anyway), so `g_ps30_main` is loaded with `FindResource(module, "G_PS30_MAIN", RT_RCDATA)`.
+/// With an object file the entry references it, otherwise the bytes are inlined.
+fn write_rc(
+    data: &[u8],
+    rc_file: &str,
+    variable_name: &str,
+    object_file: Option<&str>,
+) -> Result<(), std::io::Error> {
+    let name = variable_name.to_uppercase();
+    let mut file = BufWriter::new(create_output_file(rc_file)?);
+    if let Some(object_file) = object_file {
+        let path = object_file.replace('\\', "\\\\").replace('"', "\"\"");
+        writeln!(file, "{name} RCDATA \"{path}\"")?;
+    } else {
+        // inline data is a list of 16-bit little endian words, an odd last byte goes in a string
+        writeln!(file, "{name} RCDATA")?;
+        writeln!(file, "{{")?;
+        let words = data.chunks(2).collect::<Vec<_>>();
+        for (i, line) in words.chunks(8).enumerate() {
+            write!(file, "   ")?;
+            for (j, word) in line.iter().enumerate() {
+                let last = i * 8 + j + 1 == words.len();
+                let separator = if last { "" } else { "," };
+                match word {
+                    [low, high] => write!(
+                        file,
+                        " 0x{:04x}{separator}",
+                        u16::from_le_bytes([*low, *high])
+                    )?,
+                    [byte] => write!(file, " \"\\{byte:03o}\"")?,
+                    _ => unreachable!(),
+                }
+            }
+            writeln!(file)?;
+        }
+        writeln!(file, "}}")?;
+    }
+    file.flush()?;
+    eprintln!("Wrote resource {name} to {rc_file}");
+    Ok(())
+}
+
+/// Writes an enum of the shaders a batch compiled, for engines that index their shaders by enum.
+///
+/// Each `(variable name, /Fh header)` gets the enumerator `FXC2_SHADER_<VARIABLE_NAME>`, the variable
+/// name in upper case with anything that isn't a letter or digit replaced by `_` (`g_ps50_main`
+/// becomes `FXC2_SHADER_G_PS50_MAIN`), in the order they were compiled and followed by
+/// `FXC2_SHADER_COUNT`. The file includes every header, relative to its own directory, and
+/// `fxc2_shaders` maps each enumerator to a `struct fxc2_shader_bytes` with the `data` and `size`
+/// of its array.
+fn write_enum(
+    shaders: &[(String, String)],
+    enum_file: &str,
+    language: OutputLanguage,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let enumerator = |variable_name: &str| {
+        let name = variable_name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        format!("FXC2_SHADER_{name}")
+    };
+    let mut enumerators = Vec::new();
+    for (variable_name, _) in shaders {
+        let name = enumerator(variable_name);
+        if enumerators.contains(&name) {
+            return Err(
+                format!("more than one shader is named {name}, give them distinct /Vn").into(),
+            );
+        }
+        enumerators.push(name);
+    }
+    let enum_dir = Path::new(enum_file).parent().unwrap_or(Path::new(""));
+    let (byte, size) = match language {
+        OutputLanguage::StdArray => ("std::uint8_t", "std::size_t"),
+        _ => ("BYTE", "SIZE_T"),
+    };
+
+    let mut file = BufWriter::new(create_output_file(enum_file)?);
+    writeln!(file, "// The shaders compiled by fxc2, by enum")?;
+    writeln!(file, "#pragma once")?;
+    writeln!(file)?;
+    for (_, header) in shaders {
+        let header = depfile::relative_to(Path::new(header), enum_dir);
+        writeln!(
+            file,
+            "#include \"{}\"",
+            header.to_string_lossy().replace('\\', "/")
+        )?;
+    }
+    writeln!(file)?;
+    writeln!(file, "enum fxc2_shader")?;
+    writeln!(file, "{{")?;
+    for name in enumerators.iter() {
+        writeln!(file, "    {name},")?;
+    }
+    writeln!(file, "    FXC2_SHADER_COUNT")?;
+    writeln!(file, "}};")?;
+    writeln!(file)?;
+    writeln!(file, "struct fxc2_shader_bytes")?;
+    writeln!(file, "{{")?;
+    writeln!(file, "    const {byte}* data;")?;
+    writeln!(file, "    {size} size;")?;
+    writeln!(file, "}};")?;
+    writeln!(file)?;
+    writeln!(
+        file,
+        "static const struct fxc2_shader_bytes fxc2_shaders[FXC2_SHADER_COUNT] =\n{{"
+    )?;
+    for (variable_name, _) in shaders {
+        match language {
+            OutputLanguage::StdArray => writeln!(
+                file,
+                "    {{ {variable_name}.data(), {variable_name}.size() }},"
+            )?,
+            _ => writeln!(file, "    {{ {variable_name}, sizeof({variable_name}) }},")?,
         }
     }
+    end_initializer(&mut file, shaders.is_empty())?;
+    file.flush()?;
+    eprintln!("Wrote the enum of {} shaders to {enum_file}", shaders.len());
+    Ok(())
+}
+
+/// Writes what a compute shader needs from its pipeline as a C header.
+///
+/// `<variable_name>_pso_desc` is a `static const struct fxc2_compute_desc` with:
+/// - `threads`: the `[numthreads(x, y, z)]` of the shader
+/// - `cbv_count`, `srv_count`, `uav_count`, `sampler_count`: the number of registers of each
+///   class the shader binds (an unbounded array counts as 0)
+///
+/// and `<variable_name>_pso_bindings` a `static const struct fxc2_compute_binding` array with one
+/// entry per binding: its register class (`'b'`, `'t'`, `'u'` or `'s'`), first register, count,
+/// register space and name.
+fn write_pso_desc(
+    reflection: &ShaderReflection,
+    pso_desc_file: &str,
+    variable_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let [x, y, z] = reflection.thread_group_size();
+    let bindings = reflection.resource_bindings()?;
+    let count = |class: char| -> u32 {
+        bindings
+            .iter()
+            .filter(|binding| binding.register_class() == class)
+            .map(|binding| binding.bind_count)
+            .sum()
+    };
+    let mut file = create_output_file(pso_desc_file)?;
+
+    writeln!(file, "#ifndef FXC2_COMPUTE_DESC_DEFINED")?;
+    writeln!(file, "#define FXC2_COMPUTE_DESC_DEFINED")?;
+    writeln!(file, "struct fxc2_compute_desc")?;
+    writeln!(file, "{{")?;
+    writeln!(file, "    unsigned int threads[3];")?;
+    writeln!(file, "    unsigned int cbv_count;")?;
+    writeln!(file, "    unsigned int srv_count;")?;
+    writeln!(file, "    unsigned int uav_count;")?;
+    writeln!(file, "    unsigned int sampler_count;")?;
+    writeln!(file, "}};")?;
+    writeln!(file, "struct fxc2_compute_binding")?;
+    writeln!(file, "{{")?;
+    writeln!(file, "    char register_class;")?;
+    writeln!(file, "    unsigned int reg;")?;
+    writeln!(file, "    unsigned int count;")?;
+    writeln!(file, "    unsigned int space;")?;
+    writeln!(file, "    const char *name;")?;
+    writeln!(file, "}};")?;
+    writeln!(file, "#endif")?;
+    writeln!(file)?;
+    writeln!(
+        file,
+        "static const struct fxc2_compute_desc {variable_name}_pso_desc = {{ {{ {x}, {y}, {z} }}, {}, {}, {}, {} }};",
+        count('b'),
+        count('t'),
+        count('u'),
+        count('s')
+    )?;
+    writeln!(file)?;
+    writeln!(
+        file,
+        "static const struct fxc2_compute_binding {variable_name}_pso_bindings[] =\n{{"
+    )?;
+    for binding in bindings.iter() {
+        writeln!(
+            file,
+            "    {{ '{}', {}, {}, {}, \"{}\" }},",
+            binding.register_class(),
+            binding.bind_point,
+            binding.bind_count,
+            binding.space,
+            binding.name
+        )?;
+    }
+    end_initializer(&mut file, bindings.is_empty())?;
+    writeln!(
+        file,
+        "#define {variable_name}_pso_bindings_count {}",
+        bindings.len()
+    )?;
+
+    eprintln!("Wrote compute pipeline description to {pso_desc_file}");
+    Ok(())
+}
+
+/// Closes the initializer list of an array, giving it a zeroed element if it has none.
+fn end_initializer(file: &mut impl Write, empty: bool) -> Result<(), std::io::Error> {
+    if empty {
+        // C doesn't allow empty initializers
+        writeln!(file, "    {{ 0 }},")?;
+    }
+    writeln!(file, "}};")
+}
+
+/// Writes the input (and optionally output) signature of the shader as a C header.
+///
+/// Every signature is a `static const struct fxc2_signature_element` array named after the
+/// variable name with an `_input_signature` or `_output_signature` suffix, holding one entry per
+/// parameter:
+/// - `semantic_name`, `semantic_index`: the HLSL semantic, e.g. `"TEXCOORD", 1`
+/// - `reg`: the register the parameter occupies
+/// - `system_value`: the `D3D_NAME` of the parameter, 0 for non system values
+/// - `format`: the `DXGI_FORMAT` matching the parameter's component type and count
+/// - `mask`: the components of the register in use
+fn write_signature(
+    output: &ID3DBlob,
+    signature_file: &str,
+    variable_name: &str,
+    with_output_signature: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    fn write_elements(
+        file: &mut File,
+        name: &str,
+        parameters: &[SignatureParameter],
+    ) -> Result<(), std::io::Error> {
+        writeln!(
+            file,
+            "static const struct fxc2_signature_element {name}[] =\n{{"
+        )?;
+        for parameter in parameters {
+            let (format, format_name) = parameter.dxgi_format();
+            writeln!(
+                file,
+                "    {{ \"{}\", {}, {}, {}, {format} /* {format_name} */, 0x{:x} }},",
+                parameter.semantic_name,
+                parameter.semantic_index,
+                parameter.register,
+                parameter.system_value,
+                parameter.mask
+            )?;
+        }
+        end_initializer(file, parameters.is_empty())?;
+        writeln!(file, "#define {name}_count {}", parameters.len())
+    }
+
+    let reflection = ShaderReflection::new(blob_bytes(output))?;
+    let mut file = create_output_file(signature_file)?;
+
+    writeln!(file, "#ifndef FXC2_SIGNATURE_ELEMENT_DEFINED")?;
+    writeln!(file, "#define FXC2_SIGNATURE_ELEMENT_DEFINED")?;
+    writeln!(file, "struct fxc2_signature_element")?;
+    writeln!(file, "{{")?;
+    writeln!(file, "    const char *semantic_name;")?;
+    writeln!(file, "    unsigned int semantic_index;")?;
+    writeln!(file, "    unsigned int reg;")?;
+    writeln!(file, "    unsigned int system_value;")?;
+    writeln!(file, "    unsigned int format;")?;
+    writeln!(file, "    unsigned char mask;")?;
+    writeln!(file, "}};")?;
+    writeln!(file, "#endif")?;
+    writeln!(file)?;
+    write_elements(
+        &mut file,
+        &format!("{variable_name}_input_signature"),
+        &reflection.input_signature()?,
+    )?;
+    if with_output_signature {
+        writeln!(file)?;
+        write_elements(
+            &mut file,
+            &format!("{variable_name}_output_signature"),
+            &reflection.output_signature()?,
+        )?;
+    }
+
+    eprintln!("Wrote shader signature to {signature_file}");
+    Ok(())
+}
+
+fn write_reflection_json(
+    output: &ID3DBlob,
+    reflect_json_file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reflection = ShaderReflection::new(blob_bytes(output))?;
+    let json = reflect::to_json(&reflection)?;
+    let mut file = create_output_file(reflect_json_file)?;
+    serde_json::to_writer_pretty(&mut file, &json)?;
+    writeln!(file)?;
+    eprintln!("Wrote shader reflection to {reflect_json_file}");
+    Ok(())
+}
+
+fn write_descriptor_map(
+    output: &ID3DBlob,
+    descriptor_map_file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reflection = ShaderReflection::new(blob_bytes(output))?;
+    let json = reflect::descriptor_map(&reflection)?;
+    let mut file = create_output_file(descriptor_map_file)?;
+    serde_json::to_writer_pretty(&mut file, &json)?;
+    writeln!(file)?;
+    eprintln!("Wrote descriptor set mapping to {descriptor_map_file}");
+    Ok(())
+}
+
+fn write_bindings_hlsl(
+    output: &ID3DBlob,
+    bindings_hlsl_file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reflection = ShaderReflection::new(blob_bytes(output))?;
+    let hlsl = reflect::bindings_hlsl(&reflection)?;
+    write_file(bindings_hlsl_file, hlsl.as_bytes(), false)?;
+    eprintln!("Wrote resource registers to {bindings_hlsl_file}");
+    Ok(())
+}
+
+/// Writes a make rule making the compiled output depend on the input and everything it included.
+fn write_dep_file(
+    opts: &ParsedOptions,
+    includes: &[PathBuf],
+    dep_file: &str,
+) -> std::io::Result<()> {
+    let target = opts
+        .object_file
+        .as_deref()
+        .unwrap_or(opts.output_file.as_str());
+    let mut dependencies = vec![PathBuf::from(&opts.input_file)];
+    dependencies.extend(includes.iter().cloned());
+    let rule = depfile::make_rule(
+        target,
+        &dependencies,
+        opts.dep_relative_to.as_deref().map(Path::new),
+    );
+    write_file(dep_file, rule.as_bytes(), false)?;
+    eprintln!("Wrote dependencies to {dep_file}");
+    Ok(())
+}
+
+fn disassemble(data: &[u8]) -> windows::core::Result<String> {
+    let blob =
+        unsafe { D3DDisassemble(data.as_ptr() as *const c_void, data.len(), 0, PCSTR::null()) };
+    trace::call(
+        "D3DDisassemble",
+        format_args!("{} bytes, flags 0", data.len()),
+        &blob,
+    );
+    let blob = blob?;
+    let text = String::from_utf8_lossy(blob_bytes(&blob));
+    Ok(text.trim_end_matches('\0').to_owned())
+}
+
+/// Prints every known profile by stage, with the prefix of the variable names derived for it.
+/// Vertex, pixel and compute profiles are checked against the compiler by compiling a trivial
+/// shader, the others are only marked as depending on its version.
+fn list_profiles(default_prefix: &str) {
+    let mut stage = "";
+    for profile in PROFILES {
+        let (prefix, _) = profile.split_once('_').unwrap_or((profile, ""));
+        if prefix != stage {
+            stage = prefix;
+            let name = match stage {
+                "vs" => "Vertex shaders",
+                "ps" => "Pixel shaders",
+                "gs" => "Geometry shaders",
+                "hs" => "Hull shaders",
+                "ds" => "Domain shaders",
+                "cs" => "Compute shaders",
+                "lib" => "Libraries",
+                "fx" => "Effects",
+                "rootsig" => "Root signatures",
+                _ => stage,
+            };
+            println!("{name}:");
+        }
+        let support = match profile_supported(profile) {
+            None => "version-dependent",
+            Some(true) => "supported",
+            Some(false) => "not supported",
+        };
+        println!(
+            "  {profile:<18} {:<12} {support}",
+            format!("{}_<entry>", variable_prefix(profile, default_prefix))
+        );
+    }
+}
+
+/// Whether the compiler supports the profile, found by compiling a trivial shader for it once per
+/// process. None for the stages without a trivial shader, only vertex, pixel and compute have one.
+fn profile_supported(profile: &str) -> Option<bool> {
+    static SUPPORTED: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+
+    let (stage, _) = profile.split_once('_').unwrap_or((profile, ""));
+    let source = match stage {
+        "vs" => "float4 main() : SV_Position { return 0; }",
+        "ps" => "float4 main() : SV_Target { return 0; }",
+        "cs" => "[numthreads(1, 1, 1)] void main() {}",
+        _ => return None,
+    };
+    let mut supported = SUPPORTED.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some((_, result)) = supported.iter().find(|(known, _)| known == profile) {
+        return Some(*result);
+    }
+    let result = compiles(source, profile);
+    supported.push((profile.to_owned(), result));
+    Some(result)
+}
+
+/// The path of the compiler DLL this process loaded, to tell which copy of it is too old.
+fn compiler_path() -> Option<String> {
+    let module = unsafe { GetModuleHandleW(w!("d3dcompiler_47.dll")) }.ok()?;
+    let mut path = [0; 260];
+    let len = unsafe { GetModuleFileNameW(module, &mut path) } as usize;
+    (len > 0).then(|| String::from_utf16_lossy(&path[..len]))
+}
+
+/// Whether the compiler accepts the source for the profile.
+fn compiles(source: &str, profile: &str) -> bool {
+    let target = CString::new(profile).unwrap();
+    compile::Call {
+        source: source.as_bytes(),
+        file_name: c"profile probe",
+        defines: &[D3D_SHADER_MACRO::default()],
+        include: D3DCOMPILE_STANDARD_FILE_INCLUDE,
+        include_name: "D3D_COMPILE_STANDARD_FILE_INCLUDE",
+        entry_point: c"main",
+        profile: &target,
+        flags1: 0,
+        secondary_data_flags: 0,
+        secondary_data: &[],
+    }
+    .compile()
+    .result
+    .is_ok()
+}
+
+/// The input files of /compress and /decompress, in command line order.
+fn packed_files(opts: &ParsedOptions) -> Vec<&str> {
+    if opts.input_files.is_empty() {
+        vec![opts.input_file.as_str()]
+    } else {
+        opts.input_files.iter().map(String::as_str).collect()
+    }
+}
+
+/// Packs the compiled shaders of the input files into one blob with `D3DCompressShaders`, like
+/// fxc's /compress, and writes it to the object file.
+fn compress_shaders(opts: &ParsedOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let shaders = packed_files(opts)
+        .into_iter()
+        .map(std::fs::read)
+        .collect::<Result<Vec<_>, _>>()?;
+    // points into shaders
+    let data = shaders
+        .iter()
+        .map(|shader| D3D_SHADER_DATA {
+            pBytecode: shader.as_ptr() as *const c_void,
+            BytecodeLength: shader.len(),
+        })
+        .collect::<Vec<_>>();
+    let compressed = unsafe { D3DCompressShaders(&data, D3D_COMPRESS_SHADER_KEEP_ALL_PARTS) };
+    trace::call(
+        "D3DCompressShaders",
+        format_args!("{} shaders, D3D_COMPRESS_SHADER_KEEP_ALL_PARTS", data.len()),
+        &compressed,
+    );
+    let object_file = opts.object_file.as_deref().expect("/compress without /Fo");
+    write_object(
+        blob_bytes(&compressed?),
+        object_file,
+        false,
+        opts.write_if_changed,
+    )?;
+    Ok(())
+}
+
+/// Unpacks a blob written by /compress with `D3DDecompressShaders`, like fxc's /decompress: the
+/// first input file is the blob, and its shaders are written to the other input files in order.
+fn decompress_shaders(opts: &ParsedOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let files = packed_files(opts);
+    let (packed_file, shader_files) = files.split_first().expect("/decompress without files");
+    let packed = std::fs::read(packed_file)?;
+    let mut shaders: Vec<Option<ID3DBlob>> = vec![None; shader_files.len()];
+    let mut total = 0u32;
+    let hr = unsafe {
+        D3DDecompressShaders(
+            packed.as_ptr() as *const c_void,
+            packed.len(),
+            shaders.len() as u32,
+            0,
+            None,
+            0,
+            shaders.as_mut_ptr(),
+            Some(&mut total),
+        )
+    };
+    trace::call(
+        "D3DDecompressShaders",
+        format_args!("{} bytes, {} shaders", packed.len(), shaders.len()),
+        &hr,
+    );
+    hr?;
+    if total as usize != shader_files.len() {
+        return Err(format!(
+            "{packed_file} holds {total} shaders, but {} output files were given",
+            shader_files.len()
+        )
+        .into());
+    }
+    for (shader, shader_file) in shaders.iter().zip(shader_files) {
+        let shader = shader.as_ref().ok_or("the blob is missing a shader")?;
+        write_object(
+            blob_bytes(shader),
+            shader_file,
+            false,
+            opts.write_if_changed,
+        )?;
+    }
+    Ok(())
+}
+
+/// Lists the instructions that differ between two compiled shaders, separating the ones that only
+/// differ in precision. Returns true if there are any differences.
+fn compare_precision(
+    first_file: &str,
+    second_file: &str,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let first = disassemble(&std::fs::read(first_file)?)?;
+    let second = disassemble(&std::fs::read(second_file)?)?;
+    let differences = disasm::compare(&first, &second);
+    let (precision, other): (Vec<_>, Vec<_>) = differences
+        .iter()
+        .partition(|difference| difference.precision_only);
+    for difference in precision.iter() {
+        println!(
+            "instruction {}: `{}` vs `{}`",
+            difference.index, difference.first, difference.second
+        );
+    }
+    println!(
+        "{} instructions differ in precision, {} differ otherwise",
+        precision.len(),
+        other.len()
+    );
+    Ok(!differences.is_empty())
+}
+
+/// Finds every disassembly line using one of the forbidden tokens as a whole word.
+/// Returns a description of every occurrence.
+fn find_forbidden(disassembly: &str, forbidden: &[String]) -> Vec<String> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut occurrences = Vec::new();
+    for (i, line) in disassembly.lines().enumerate() {
+        let code = line.trim();
+        if code.starts_with("//") {
+            continue;
+        }
+        for token in forbidden {
+            let found = code.match_indices(token.as_str()).any(|(start, _)| {
+                let end = start + token.len();
+                !code[..start].ends_with(is_word) && !code[end..].starts_with(is_word)
+            });
+            if found {
+                occurrences.push(format!("line {}: '{token}' in `{code}`", i + 1));
+            }
+        }
+    }
+    occurrences
+}
+
+fn write_meta(
+    opts: &ParsedOptions,
+    output: &ID3DBlob,
+    meta_file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let input = std::fs::read(&opts.input_file)?;
+    let timestamp = if opts.meta_timestamp {
+        Some(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+    } else {
+        None
+    };
+    let json = meta::to_json(opts, &input, blob_bytes(output), timestamp);
+    let mut file = create_output_file(meta_file)?;
+    serde_json::to_writer_pretty(&mut file, &json)?;
+    writeln!(file)?;
+    eprintln!("Wrote compile metadata to {meta_file}");
+    Ok(())
+}
+
+/// Describes the /Fh and /Fo files a compile of a batch wrote, for the manifest.
+fn manifest_entries(opts: &ParsedOptions) -> std::io::Result<Vec<serde_json::Value>> {
+    let outputs = [
+        (
+            "header",
+            Some(&opts.output_file).filter(|file| !file.is_empty()),
+        ),
+        ("object", opts.object_file.as_ref()),
+    ];
+    let mut entries = Vec::new();
+    for (kind, path) in outputs {
+        if let Some(path) = path {
+            let contents = std::fs::read(path)?;
+            entries.push(meta::manifest_entry(opts, kind, path, &contents));
+        }
+    }
+    Ok(entries)
+}
+
+fn write_manifest(
+    opts: &ParsedOptions,
+    artifacts: Vec<serde_json::Value>,
+    manifest_file: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let timestamp = if opts.meta_timestamp {
+        Some(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+    } else {
+        None
+    };
+    let json = meta::manifest(artifacts, timestamp);
+    let mut file = create_output_file(manifest_file)?;
+    serde_json::to_writer_pretty(&mut file, &json)?;
+    writeln!(file)?;
+    eprintln!("Wrote the manifest to {manifest_file}");
+    Ok(())
+}
+
+/// Reports the size of every constant buffer and their total, and returns the total.
+fn report_cbuffer_sizes(output: &ID3DBlob, budget: u32) -> windows::core::Result<u32> {
+    let buffers = ShaderReflection::new(blob_bytes(output))?.constant_buffers()?;
+    let mut total = 0;
+    for buffer in buffers.iter() {
+        println!("Constant buffer {}: {} bytes", buffer.name, buffer.size);
+        total += buffer.size;
+    }
+    println!("Constant buffers total: {total} bytes of a {budget} byte budget");
+    Ok(total)
+}
+
+/// Checks the shader's resource bindings against the expected ones.
+/// Returns a description of every mismatch.
+fn check_bindings(
+    output: &ID3DBlob,
+    expected_bindings: &[ExpectedBinding],
+) -> windows::core::Result<Vec<String>> {
+    let resources = ShaderReflection::new(blob_bytes(output))?.resource_bindings()?;
+    let mut mismatches = Vec::new();
+    for expected in expected_bindings {
+        let Some(resource) = resources.iter().find(|i| i.name == expected.name) else {
+            mismatches.push(format!("'{}' is not bound by the shader", expected.name));
+            continue;
+        };
+        if resource.bind_point != expected.slot {
+            mismatches.push(format!(
+                "'{}' is bound at slot {}, expected {}",
+                expected.name, resource.bind_point, expected.slot
+            ));
+        }
+        if let Some(space) = expected.space.filter(|space| *space != resource.space) {
+            mismatches.push(format!(
+                "'{}' is bound in space {}, expected {}",
+                expected.name, resource.space, space
+            ));
+        }
+    }
+    Ok(mismatches)
+}
+
+fn main() -> ExitCode {
+    // ====================================================================================
+    // Shader Compilation
+
+    let args = match ParseOpt::new() {
+        Ok(args) => args,
+        Err(err) => {
+            if wants_porcelain() {
+                println!(
+                    "FXC2-FAIL usage: {}",
+                    err.to_string().lines().next().unwrap_or_default()
+                );
+            }
+            return err.into();
+        }
+    };
+
+    if args.opts.list_profiles {
+        list_profiles(&args.opts.default_prefix);
+        return ExitCode::SUCCESS;
+    }
+
+    if let Some(first_file) = &args.opts.compare_precision {
+        return match compare_precision(first_file, &args.opts.input_file) {
+            Ok(true) if args.opts.strict => ExitCode::FAILURE,
+            Ok(_) => ExitCode::SUCCESS,
+            Err(err) => {
+                print_failure("compare shaders", err);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let result = if args.opts.compress || args.opts.decompress {
+        let packed = if args.opts.compress {
+            compress_shaders(&args.opts)
+        } else {
+            decompress_shaders(&args.opts)
+        };
+        match packed {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                let action = if args.opts.compress {
+                    "compress"
+                } else {
+                    "decompress"
+                };
+                print_failure(&format!("{action} shaders"), err);
+                ExitCode::FAILURE
+            }
+        }
+    } else if let Some(techniques_file) = &args.opts.techniques_file {
+        compile_techniques(&args, techniques_file)
+    } else if let Some(files_from) = &args.opts.files_from {
+        compile_files(&args, files_from)
+    } else if !args.opts.input_files.is_empty() {
+        let files = args.opts.input_files.iter().map(String::as_str);
+        compile_file_list(&args, &files.collect::<Vec<_>>())
+    } else if args.opts.porcelain {
+        run_porcelain(&args)
+    } else {
+        run(&args)
+    };
+    if timings::enabled() {
+        timings::print_table();
+    }
+    result
+}
+
+/// Whether --porcelain was given, on the command line or in a response file, for when the options
+/// don't parse.
+fn wants_porcelain() -> bool {
+    let command_line = env::args().skip(1).collect::<Vec<_>>();
+    let args = expand_response_files(&command_line, |file| std::fs::read_to_string(file))
+        .unwrap_or(command_line);
+    args.iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == "--porcelain")
+}
+
+/// Like [`run`], but prints a single line to stdout for tools to parse:
+/// - `FXC2-OK <output> <bytes> <md5>` on success, with the path of the /Fo object (or of the /Fh
+///   header without /Fo), its size and the MD5 of its contents in hex
+/// - `FXC2-FAIL <reason>` on failure, where the reason starts with the step that failed: `usage`,
+///   `compile` (followed by the compiler's first error) or `output`
+///
+/// The details of a failure are still reported on stderr. The options that print anything else to
+/// stdout, or don't write a /Fo or /Fh file, can't be used with --porcelain.
+fn run_porcelain(args: &ParseOpt) -> ExitCode {
+    let output = match compile_shader(args) {
+        Ok(output) => output,
+        Err(reason) => {
+            println!("FXC2-FAIL compile: {reason}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if write_outputs(args, output) != ExitCode::SUCCESS {
+        println!("FXC2-FAIL output");
+        return ExitCode::FAILURE;
+    }
+
+    let opts = &args.opts;
+    let output_path = opts.object_file.as_deref().unwrap_or(&opts.output_file);
+    match std::fs::read(output_path) {
+        Ok(data) => {
+            let hash = dxbc::md5(&data)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>();
+            println!("FXC2-OK {output_path} {} {hash}", data.len());
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            print_failure("read back the output", err);
+            println!("FXC2-FAIL output");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Compiles every technique of the techniques file from the input, as if fxc2 ran once per
+/// technique with its `/E` and `/T` added, and `/Fh` and `/Fo` named after its entry point. The
+/// other options, outputs included, apply to every technique as given.
+///
+/// Each technique's arguments are parsed on their own, so without `/Vn` every header gets the
+/// variable name derived for its own profile, e.g. `g_vs30_main` and `g_ps30_main`.
+fn compile_techniques(args: &ParseOpt, techniques_file: &str) -> ExitCode {
+    let techniques = std::fs::read_to_string(techniques_file)
+        .map_err(|err| err.to_string())
+        .and_then(|text| techniques::parse(&text));
+    let techniques = match techniques {
+        Ok(techniques) => techniques,
+        Err(err) => {
+            print_failure("read techniques file", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    run_batch(args, "techniques", &technique_jobs(&args.opts, &techniques))
+}
+
+/// A batch job per technique, see [`compile_techniques`].
+fn technique_jobs(opts: &ParsedOptions, techniques: &[Technique]) -> Vec<BatchJob> {
+    techniques
+        .iter()
+        .map(|technique| {
+            let entry_point = &technique.entry_point;
+            let mut job_args = vec![
+                "/E".to_owned(),
+                entry_point.clone(),
+                "/T".to_owned(),
+                technique.profile.clone(),
+            ];
+            if !opts.output_file.is_empty() {
+                job_args.push("/Fh".to_owned());
+                job_args.push(techniques::output_name(&opts.output_file, entry_point));
+            }
+            if let Some(object_file) = &opts.object_file {
+                job_args.push("/Fo".to_owned());
+                job_args.push(techniques::output_name(object_file, entry_point));
+            }
+            BatchJob {
+                name: format!("{entry_point} ({})", technique.profile),
+                args: job_args,
+            }
+        })
+        .collect()
+}
+
+/// Compiles every file listed in the --files-from list (`-` for stdin) with the other options, one
+/// path per line, or per NUL byte with --null. See [`compile_file_list`] for the outputs.
+fn compile_files(args: &ParseOpt, files_from: &str) -> ExitCode {
+    // checked here, since every file's own compile has both
+    let input_file = args
+        .opts
+        .input_files
+        .first()
+        .unwrap_or(&args.opts.input_file);
+    if !input_file.is_empty() {
+        return UsageError::ConflictingArguments("--files-from".to_owned(), input_file.clone())
+            .into();
+    }
+    let mut list = Vec::new();
+    let read = if files_from == "-" {
+        std::io::stdin().read_to_end(&mut list)
+    } else {
+        File::open(files_from).and_then(|mut file| file.read_to_end(&mut list))
+    };
+    if let Err(err) = read {
+        print_failure("read the file list", err);
+        return ExitCode::FAILURE;
+    }
+    let list = String::from_utf8_lossy(&list);
+    let files = if args.opts.null_separated {
+        list.split('\0').collect::<Vec<_>>()
+    } else {
+        list.lines().collect::<Vec<_>>()
+    };
+    let files = files
+        .into_iter()
+        .filter(|file| !file.is_empty())
+        .collect::<Vec<_>>();
+    compile_file_list(args, &files)
+}
+
+/// Compiles each file with the other options, from --files-from or several input files. `{name}`
+/// in `/Fh` and `/Fo` is replaced by each file's name without its extension, so
+/// `/Fh out/{name}.h` writes `out/blur.h` for `blur.hlsl`.
+fn compile_file_list(args: &ParseOpt, files: &[&str]) -> ExitCode {
+    let opts = &args.opts;
+    let named_outputs = [Some(&opts.output_file), opts.object_file.as_ref()];
+    if files.len() > 1
+        && named_outputs
+            .iter()
+            .flatten()
+            .any(|output| !output.is_empty() && !output.contains("{name}"))
+    {
+        print_failure(
+            "compile the file list",
+            "/Fh and /Fo need {name} in their path, or every file overwrites the same output",
+        );
+        return ExitCode::FAILURE;
+    }
+
+    let jobs = files
+        .iter()
+        .map(|file| {
+            let name = Path::new(file)
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy();
+            let mut job_args = Vec::new();
+            if !opts.output_file.is_empty() {
+                job_args.push("/Fh".to_owned());
+                job_args.push(opts.output_file.replace("{name}", &name));
+            }
+            if let Some(object_file) = &opts.object_file {
+                job_args.push("/Fo".to_owned());
+                job_args.push(object_file.replace("{name}", &name));
+            }
+            job_args.push("--".to_owned());
+            job_args.push(file.to_string());
+            BatchJob {
+                name: file.to_string(),
+                args: job_args,
+            }
+        })
+        .collect::<Vec<_>>();
+    run_batch(args, "files", &jobs)
+}
+
+/// One compile of a batch.
+struct BatchJob {
+    /// What the results call it
+    name: String,
+    /// The arguments added to the invocation's for this compile, later ones win
+    args: Vec<String>,
+}
+
+impl BatchJob {
+    /// The invocation's arguments with the job's added.
+    fn args_for(&self, args: &ParseOpt) -> Vec<String> {
+        // the job's options go before a `--`, everything after it is an input file
+        let mut job_args = if args.opts.input_files.is_empty() {
+            args.args.clone()
+        } else {
+            // each job compiles one of them
+            without_input_files(&args.args)
+        };
+        let separated = match job_args.iter().position(|arg| arg == "--") {
+            Some(separator) => job_args.split_off(separator),
+            None => Vec::new(),
+        };
+        job_args.extend(self.args.iter().cloned());
+        // the job can bring its own `--`
+        let skip = usize::from(self.args.iter().any(|arg| arg == "--"));
+        job_args.extend(separated.into_iter().skip(skip));
+        job_args
+    }
+}
+
+/// Runs each job as if fxc2 was invoked with the invocation's arguments plus the job's, and reports
+/// which of the `what` failed.
+fn run_batch(args: &ParseOpt, what: &str, jobs: &[BatchJob]) -> ExitCode {
+    let mut failed = Vec::new();
+    let mut artifacts = Vec::new();
+    let mut shaders = Vec::new();
+    for job in jobs {
+        eprintln!("{}:", job.name);
+        let result = match ParseOpt::from_args(job.args_for(args)) {
+            Ok(job_args) => {
+                let mut result = run(&job_args);
+                if result == ExitCode::SUCCESS && args.opts.enum_file.is_some() {
+                    shaders.push((
+                        job_args.opts.variable_name.clone(),
+                        job_args.opts.output_file.clone(),
+                    ));
+                }
+                if result == ExitCode::SUCCESS && args.opts.manifest_file.is_some() {
+                    match manifest_entries(&job_args.opts) {
+                        Ok(entries) => artifacts.extend(entries),
+                        Err(err) => {
+                            print_failure("read back the outputs for the manifest", err);
+                            result = ExitCode::FAILURE;
+                        }
+                    }
+                }
+                result
+            }
+            Err(err) => err.into(),
+        };
+        if result != ExitCode::SUCCESS {
+            failed.push(job.name.as_str());
+        }
+    }
+
+    if let Some(enum_file) = &args.opts.enum_file {
+        if let Err(err) = write_enum(&shaders, enum_file, args.opts.language) {
+            print_failure("write the shader enum", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    // even if some failed, the manifest lists what the others wrote
+    if let Some(manifest_file) = &args.opts.manifest_file {
+        if let Err(err) = write_manifest(&args.opts, artifacts, manifest_file) {
+            print_failure("write the manifest", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    eprintln!(
+        "Compiled {} of {} {what}",
+        jobs.len() - failed.len(),
+        jobs.len()
+    );
+    if !failed.is_empty() {
+        eprintln!("Failed {what}: {}", failed.join(", "));
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Compiles the shader and writes everything the options ask for.
+fn run(args: &ParseOpt) -> ExitCode {
+    match compile_shader(args) {
+        Ok(output) => write_outputs(args, output),
+        Err(_) => ExitCode::FAILURE,
+    }
+}
+
+/// The exit code when a compile runs past --timeout, the same as coreutils' `timeout`.
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// Compiles on a worker thread, waiting at most `timeout` for it.
+///
+/// D3DCompile2 can't be interrupted, so when the time is up the process exits with
+/// [`TIMEOUT_EXIT_CODE`] while the compile thread is still running. A batch stops at the first job
+/// that times out. The thread works on its own copy of the options and sends back plain bytes, the
+/// blobs are created again on this thread.
+fn compile_with_timeout(
+    args: &ParseOpt,
+    preprocess: bool,
+    timeout: Duration,
+) -> (Result<(), windows::core::Error>, CompileOutput) {
+    let (opts, command_line) = (args.opts.clone(), args.args.clone());
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let compiler = thread::spawn(move || {
+        let args = ParseOpt::with_options(opts, command_line);
+        let (result, output) = args.compile(preprocess);
+        let bytes = |blob: &Option<ID3DBlob>| blob.as_ref().map(|blob| blob_bytes(blob).to_vec());
+        // the receiver is gone once the compile timed out
+        let _ = sender.send((
+            result.map_err(|err| err.code()),
+            bytes(&output.data),
+            bytes(&output.errors),
+            output.includes,
+        ));
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok((result, data, errors, includes)) => {
+            let blob = |bytes: Option<Vec<u8>>| bytes.map(|bytes| create_blob(&bytes)).transpose();
+            match (blob(data), blob(errors)) {
+                (Ok(data), Ok(errors)) => (
+                    result.map_err(windows::core::Error::from),
+                    CompileOutput {
+                        data,
+                        errors,
+                        includes,
+                    },
+                ),
+                (Err(err), _) | (_, Err(err)) => (Err(err), CompileOutput::default()),
+            }
+        }
+        Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => match compiler.join() {
+            Err(panic) => std::panic::resume_unwind(panic),
+            Ok(()) => unreachable!("the compile thread always sends its result"),
+        },
+        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+            let reason = format!(
+                "Compiling {} took longer than {} seconds, giving up",
+                args.opts.input_file,
+                timeout.as_secs()
+            );
+            eprintln!("{reason}");
+            if args.opts.porcelain {
+                println!("FXC2-FAIL timeout: {reason}");
+            }
+            std::process::exit(TIMEOUT_EXIT_CODE);
+        }
+    }
+}
+
+/// Compiles the shader, and prints the compiler's errors or warnings. On failure, returns a
+/// one-line reason for --porcelain.
+fn compile_shader(args: &ParseOpt) -> Result<CompileOutput, String> {
+    let preprocess = args.opts.preprocess_file.is_some() || args.opts.summary;
+    // an older DLL only fails with E_FAIL and a vague message for a profile it doesn't know
+    if !args.opts.dump_bin && !preprocess && profile_supported(&args.opts.model) == Some(false) {
+        let reason = format!(
+            "{} doesn't support the profile {}, it's likely older than the profile",
+            compiler_path().as_deref().unwrap_or("d3dcompiler_47.dll"),
+            args.opts.model
+        );
+        eprintln!("{reason}");
+        return Err(reason);
+    }
+    let (result, output) = match args.opts.timeout {
+        Some(timeout) => compile_with_timeout(args, preprocess, Duration::from_secs(timeout)),
+        None => args.compile(preprocess),
+    };
+    if let Some(temps_dir) = args
+        .opts
+        .temps_dir
+        .as_ref()
+        .filter(|_| !args.opts.dump_bin && !preprocess)
+    {
+        if let Err(err) = keep_preprocessed(args, temps_dir) {
+            print_failure("keep the preprocessed source", &err);
+            // a failed compile reports its own error instead
+            if result.is_ok() {
+                return Err(format!("can't keep the preprocessed source: {err}"));
+            }
+        }
+    }
+    if let Some(errors_blob_file) = &args.opts.errors_blob_file {
+        // written even when empty, so that it never holds a previous compile's messages
+        let errors = output.errors.as_ref().map(blob_bytes).unwrap_or_default();
+        if let Err(err) = write_file(errors_blob_file, errors, false) {
+            print_failure("write the error blob", &err);
+            // a failed compile reports its own error instead
+            if result.is_ok() {
+                return Err(format!("can't write the error blob: {err}"));
+            }
+        }
+    }
+    match (result, output) {
+        (Ok(()), output) => {
+            let warnings = output.errors.as_ref().map(|warnings| {
+                unsafe { CStr::from_ptr(warnings.GetBufferPointer() as *const i8) }
+                    .to_string_lossy()
+            });
+            if let Some(warnings) = &warnings {
+                print_warning(args.opts.warnings_to, warnings);
+            }
+            if let Some(max_warnings) = args.opts.max_warnings {
+                let count = diagnostics::parse(warnings.as_deref().unwrap_or_default())
+                    .filter(|diagnostic| diagnostic.severity == Severity::Warning)
+                    .count();
+                if count > max_warnings {
+                    let reason = format!(
+                        "The compiler reported {count} warnings, over the limit of {max_warnings}"
+                    );
+                    eprintln!("{reason}");
+                    return Err(reason);
+                }
+                println!(
+                    "The compiler reported {count} warnings, within the limit of {max_warnings}"
+                );
+            }
+            Ok(output)
+        }
+        (Err(err), output) => {
+            eprintln!("Got an error while compiling:");
+            eprintln!("{}", err);
+            if args.opts.why_failed {
+                let code = err.code();
+                match hresult::explain(code) {
+                    Some(explanation) => eprintln!("{explanation} (HRESULT 0x{:08X})", code.0),
+                    None => eprintln!("No explanation for HRESULT 0x{:08X}", code.0),
+                }
+            }
+            let mut reason = err.message().to_string();
+            if let Some(errors) = output.errors {
+                let error = unsafe { CStr::from_ptr(errors.GetBufferPointer() as *const i8) };
+                let error = error.to_string_lossy();
+                print_compiler_messages(args.opts.warnings_to, &error);
+                let first_error = diagnostics::parse(&error)
+                    .find(|diagnostic| diagnostic.severity == Severity::Error)
+                    .map(|diagnostic| format!("{}: {}", diagnostic.code, diagnostic.message));
+                if let Some(first_error) = first_error {
+                    reason = first_error;
+                }
+            } else {
+                eprintln!("No error message from the function");
+            }
+            Err(reason.replace(['\r', '\n'], " ").trim().to_owned())
+        }
+    }
+}
+
+/// Writes everything the options ask for from the compiled shader.
+fn write_outputs(args: &ParseOpt, output: CompileOutput) -> ExitCode {
+    if args.opts.check {
+        return ExitCode::SUCCESS;
+    }
+
+    let includes = output.includes;
+    let mut output = output.data.unwrap();
+    let opts = &args.opts;
+
+    if let Some(preprocess_file) = &opts.preprocess_file {
+        let _timer = timings::start(Phase::Write);
+        if let Err(err) = write_preprocessed(&output, preprocess_file, opts.strip_line_directives) {
+            print_failure("write preprocessed file", err);
+            return ExitCode::FAILURE;
+        }
+    }
+    if opts.summary {
+        let text = String::from_utf8_lossy(blob_bytes(&output));
+        print_summary(&summary::scan(text.trim_end_matches('\0')));
+    }
+    if opts.preprocess_file.is_some() || opts.summary {
+        return ExitCode::SUCCESS;
+    }
+
+    if opts.strip_root_signature {
+        let _timer = timings::start(Phase::Strip);
+        let stripped = dxbc::remove_part(blob_bytes(&output), dxbc::ROOT_SIGNATURE)
+            .ok_or_else(|| windows::core::Error::from(E_INVALIDARG))
+            .and_then(|stripped| create_blob(&stripped));
+        match stripped {
+            Ok(stripped) => output = stripped,
+            Err(err) => {
+                print_failure("strip the root signature", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Some(root_signature_file) = &opts.set_root_signature_file {
+        let root_signature = match std::fs::read(root_signature_file) {
+            Ok(root_signature) => root_signature,
+            Err(err) => {
+                print_failure("read the root signature", err);
+                return ExitCode::FAILURE;
+            }
+        };
+        let with_root_signature = set_blob_part(
+            blob_bytes(&output),
+            D3D_BLOB_ROOT_SIGNATURE,
+            "D3D_BLOB_ROOT_SIGNATURE",
+            &root_signature,
+        );
+        match with_root_signature {
+            Ok(with_root_signature) => output = with_root_signature,
+            Err(err) => {
+                print_failure("set the root signature", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Some(root_signature_file) = &opts.extract_root_signature_file {
+        let root_signature = get_blob_part(
+            blob_bytes(&output),
+            D3D_BLOB_ROOT_SIGNATURE,
+            "D3D_BLOB_ROOT_SIGNATURE",
+        );
+        let Ok(root_signature) = root_signature else {
+            eprintln!("The shader has no root signature");
+            return ExitCode::FAILURE;
+        };
+        match write_file(
+            root_signature_file,
+            blob_bytes(&root_signature),
+            opts.write_if_changed,
+        ) {
+            Ok(true) => eprintln!("Wrote the root signature to {root_signature_file}"),
+            Ok(false) => eprintln!("Root signature in {root_signature_file} is unchanged"),
+            Err(err) => {
+                print_failure("write the root signature", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Some(root_signature_file) = &opts.verify_root_signature_file {
+        match verify_root_signature(blob_bytes(&output), root_signature_file) {
+            Ok(true) => {}
+            Ok(false) => return ExitCode::FAILURE,
+            Err(err) => {
+                print_failure("verify the root signature", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    // strip_shader re-signs the container, so a stripped /dumpbin input stays loadable
+    if opts.strip_flags != 0 {
+        let _timer = timings::start(Phase::Strip);
+        let stripped = strip_shader(blob_bytes(&output), opts.strip_flags as i32)
+            .and_then(|stripped| create_blob(&stripped));
+        match stripped {
+            Ok(stripped) => output = stripped,
+            Err(err) => {
+                print_failure("strip the shader", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Some(note) = &opts.note {
+        // there's a single private data part, so this replaces anything already stored there
+        let noted = set_blob_part(
+            blob_bytes(&output),
+            D3D_BLOB_PRIVATE_DATA,
+            "D3D_BLOB_PRIVATE_DATA",
+            note.as_bytes(),
+        );
+        match noted {
+            Ok(noted) => output = noted,
+            Err(err) => {
+                print_failure("store the note", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Some(private_file) = &opts.set_private_file {
+        let private_data = match std::fs::read(private_file) {
+            Ok(private_data) => private_data,
+            Err(err) => {
+                print_failure("read the private data", err);
+                return ExitCode::FAILURE;
+            }
+        };
+        let with_private = set_blob_part(
+            blob_bytes(&output),
+            D3D_BLOB_PRIVATE_DATA,
+            "D3D_BLOB_PRIVATE_DATA",
+            &private_data,
+        );
+        match with_private {
+            Ok(with_private) => output = with_private,
+            Err(err) => {
+                print_failure("store the private data", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Some(private_file) = &opts.get_private_file {
+        let private_data = get_blob_part(
+            blob_bytes(&output),
+            D3D_BLOB_PRIVATE_DATA,
+            "D3D_BLOB_PRIVATE_DATA",
+        );
+        let Ok(private_data) = private_data else {
+            eprintln!("The shader has no private data");
+            return ExitCode::FAILURE;
+        };
+        match write_file(
+            private_file,
+            blob_bytes(&private_data),
+            opts.write_if_changed,
+        ) {
+            Ok(true) => eprintln!("Wrote the private data to {private_file}"),
+            Ok(false) => eprintln!("Private data in {private_file} is unchanged"),
+            Err(err) => {
+                print_failure("write the private data", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Some(length) = opts.hexdump {
+        let data = blob_bytes(&output);
+        let data = &data[..length.unwrap_or(data.len()).min(data.len())];
+        if opts.clipboard {
+            let mut text = Vec::new();
+            write_hexdump(&mut text, data).expect("Writing to memory can't fail");
+            if let Err(err) = copy_to_clipboard(&text) {
+                print_failure("copy hexdump to the clipboard", err);
+                return ExitCode::FAILURE;
+            }
+            eprintln!("Copied hexdump to the clipboard");
+        } else if let Err(err) = write_hexdump(&mut std::io::stdout().lock(), data) {
+            print_failure("write hexdump", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if opts.show_hash {
+        match dxbc::stored_checksum(blob_bytes(&output)) {
+            Some(hash) => {
+                let hash = hash
+                    .iter()
+                    .map(|byte| format!("{byte:02x}"))
+                    .collect::<String>();
+                println!("Embedded shader hash: {hash}");
+            }
+            None => {
+                eprintln!("The shader isn't a DXBC container, it has no embedded hash");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if opts.dump_sections {
+        let Some(layout) = dxbc::layout(blob_bytes(&output)) else {
+            eprintln!("The shader isn't a DXBC container, it has no sections");
+            return ExitCode::FAILURE;
+        };
+        println!("{:<8} {:>10} {:>10}", "FOURCC", "OFFSET", "SIZE");
+        for section in layout.sections.iter() {
+            println!(
+                "{:<8} {:>10} {:>10}",
+                String::from_utf8_lossy(&section.fourcc),
+                section.offset,
+                section.size
+            );
+        }
+        println!(
+            "Container: {} bytes, the header claims {} bytes",
+            blob_bytes(&output).len(),
+            layout.claimed_size
+        );
+        for problem in layout.problems.iter() {
+            print_warning(opts.warnings_to, &format!("warning: {problem}"));
+        }
+    }
+
+    if opts.show_note {
+        let note = get_blob_part(
+            blob_bytes(&output),
+            D3D_BLOB_PRIVATE_DATA,
+            "D3D_BLOB_PRIVATE_DATA",
+        );
+        match note {
+            Ok(note) => println!("Note: {}", String::from_utf8_lossy(blob_bytes(&note))),
+            Err(_) => {
+                eprintln!("The shader has no note");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Some(expected_profile) = &opts.expected_profile {
+        match ShaderReflection::new(blob_bytes(&output)) {
+            Ok(reflection) if reflection.profile() == *expected_profile => {}
+            Ok(reflection) => {
+                eprintln!(
+                    "Shader was compiled for profile '{}', expected '{expected_profile}'",
+                    reflection.profile()
+                );
+                return ExitCode::FAILURE;
+            }
+            Err(err) => {
+                print_failure("reflect shader profile", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Some(budget) = opts.cbuffer_budget {
+        match report_cbuffer_sizes(&output, budget) {
+            Ok(total) if total > budget => {
+                let excess = format!(
+                    "Constant buffers exceed the budget by {} bytes",
+                    total - budget
+                );
+                if opts.cbuffer_budget_error {
+                    eprintln!("{excess}");
+                    return ExitCode::FAILURE;
+                }
+                print_warning(opts.warnings_to, &format!("warning: {excess}"));
+            }
+            Ok(_) => {}
+            Err(err) => {
+                print_failure("reflect constant buffers", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Some(max_instructions) = opts.max_instructions {
+        match ShaderReflection::new(blob_bytes(&output)) {
+            Ok(reflection) => {
+                let count = reflection.instruction_count();
+                if count > max_instructions {
+                    eprintln!(
+                        "The shader has {count} instructions, over the limit of {max_instructions}"
+                    );
+                    return ExitCode::FAILURE;
+                }
+                println!(
+                    "The shader has {count} instructions, within the limit of {max_instructions}"
+                );
+            }
+            Err(err) => {
+                print_failure("reflect the instruction count", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if !opts.forbidden.is_empty() {
+        match disassemble(blob_bytes(&output)) {
+            Ok(disassembly) => {
+                let occurrences = find_forbidden(&disassembly, &opts.forbidden);
+                if !occurrences.is_empty() {
+                    eprintln!("Shader uses forbidden instructions:");
+                    for occurrence in occurrences {
+                        eprintln!("    {occurrence}");
+                    }
+                    return ExitCode::FAILURE;
+                }
+            }
+            Err(err) => {
+                print_failure("disassemble shader", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if !opts.expected_bindings.is_empty() {
+        match check_bindings(&output, &opts.expected_bindings) {
+            Ok(mismatches) if mismatches.is_empty() => {}
+            Ok(mismatches) => {
+                eprintln!("Shader bindings don't match the expected ones:");
+                for mismatch in mismatches {
+                    eprintln!("    {mismatch}");
+                }
+                return ExitCode::FAILURE;
+            }
+            Err(err) => {
+                print_failure("reflect shader bindings", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    // the object is stripped up front, so --max-bytes can check the size that ships
+    let mut object = None;
+    if opts.object_file.is_some() {
+        let mut data = blob_bytes(&output).to_vec();
+        if opts.minimal {
+            let _timer = timings::start(Phase::Strip);
+            let strip_flags = D3DCOMPILER_STRIP_DEBUG_INFO.0
+                | D3DCOMPILER_STRIP_REFLECTION_DATA.0
+                | D3DCOMPILER_STRIP_PRIVATE_DATA.0
+                | D3DCOMPILER_STRIP_TEST_BLOBS.0;
+            match strip_shader(&data, strip_flags) {
+                Ok(stripped) => {
+                    eprintln!(
+                        "Stripped shader object from {} to {} bytes",
+                        data.len(),
+                        stripped.len()
+                    );
+                    data = stripped;
+                }
+                Err(err) => {
+                    print_failure("strip shader object", err);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        if opts.split_debug {
+            let _timer = timings::start(Phase::Strip);
+            let object_file = opts.object_file.as_deref().unwrap_or_default();
+            match split_debug(&data, object_file, opts.write_if_changed) {
+                Ok(stripped) => data = stripped,
+                Err(err) => {
+                    print_failure("split the debug information off the shader object", err);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        object = Some(data);
+    }
+
+    if let Some(max_bytes) = opts.max_bytes {
+        let size = object.as_ref().map_or(blob_bytes(&output).len(), Vec::len);
+        if size > max_bytes {
+            eprintln!("The shader is {size} bytes, over the limit of {max_bytes} bytes");
+            return ExitCode::FAILURE;
+        }
+        println!("The shader is {size} bytes, within the limit of {max_bytes} bytes");
+    }
+
+    if let Some(golden_file) = &opts.golden_file {
+        let data = object.as_deref().unwrap_or(blob_bytes(&output));
+        if opts.update_golden {
+            let _timer = timings::start(Phase::Write);
+            if let Err(err) = write_file(golden_file, data, opts.write_if_changed) {
+                print_failure("update the golden file", err);
+                return ExitCode::FAILURE;
+            }
+            eprintln!("Updated the golden file {golden_file}");
+        } else {
+            let golden = match std::fs::read(golden_file) {
+                Ok(golden) => golden,
+                Err(err) => {
+                    print_failure("read the golden file", err);
+                    return ExitCode::FAILURE;
+                }
+            };
+            // anything that isn't a container (a /dumpbin of raw bytes) has to match exactly
+            let differences = dxbc::differing_parts(&golden, data, dxbc::VOLATILE_PARTS)
+                .unwrap_or_else(|| match golden == data {
+                    true => Vec::new(),
+                    false => vec!["the contents differ".to_owned()],
+                });
+            if !differences.is_empty() {
+                eprintln!("The shader doesn't match the golden file {golden_file}:");
+                for difference in differences.iter() {
+                    eprintln!("  {difference}");
+                }
+                eprintln!("Pass --update-golden if the change is intended");
+                return ExitCode::FAILURE;
+            }
+            eprintln!("The shader matches the golden file {golden_file}");
+        }
+    }
+
+    let mut threads = None;
+    if opts.threadgroup {
+        match ShaderReflection::new(blob_bytes(&output)) {
+            Ok(reflection) if reflection.profile().starts_with("cs_") => {
+                let size = reflection.thread_group_size();
+                println!("Thread group size: {}, {}, {}", size[0], size[1], size[2]);
+                threads = Some(size);
+            }
+            Ok(reflection) => print_warning(
+                opts.warnings_to,
+                &format!(
+                    "warning: --threadgroup only applies to compute shaders, not {}",
+                    reflection.profile()
+                ),
+            ),
+            Err(err) => {
+                print_failure("reflect the thread group size", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    // everything from here on writes the outputs
+    let _timer = timings::start(Phase::Write);
+
+    // with --clipboard the header goes there instead, unless the hexdump already did
+    let header_to_clipboard = opts.clipboard && opts.hexdump.is_none();
+    if !opts.output_file.is_empty() || header_to_clipboard {
+        let loader = if opts.emit_loader && opts.language != OutputLanguage::C {
+            print_warning(
+                opts.warnings_to,
+                "warning: not emitting a loader, it's only available for C output",
+            );
+            None
+        } else if opts.emit_loader {
+            match loader_snippet(&opts.model, &opts.variable_name) {
+                Ok(loader) => Some(loader),
+                Err(err) => {
+                    print_warning(
+                        opts.warnings_to,
+                        &format!("warning: not emitting a loader, {err}"),
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let threads =
+            threads.map(|threads| threads_declaration(threads, &opts.variable_name, opts.language));
+        let disassembly = if opts.embed_disassembly {
+            match disassemble(blob_bytes(&output)) {
+                // in a comment, which every output language has the same way
+                Ok(text) => Some(format!("/*\n{}\n*/", text.replace("*/", "* /"))),
+                Err(err) => {
+                    print_failure("disassemble the shader", err);
+                    return ExitCode::FAILURE;
+                }
+            }
+        } else {
+            None
+        };
+        let trailer = [threads, loader, disassembly]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        let trailer = (!trailer.is_empty()).then(|| trailer.join("\n"));
+        let style = ArrayStyle {
+            compact: opts.compact,
+            language: opts.language,
+            byte_width: opts.byte_width,
+            indent: &opts.indent,
+        };
+        if header_to_clipboard {
+            let mut text = Vec::new();
+            write_array(&mut text, blob_bytes(&output), &opts.variable_name, &style)
+                .expect("Writing to memory can't fail");
+            if let Some(trailer) = &trailer {
+                text.extend_from_slice(format!("\n\n{trailer}").as_bytes());
+            }
+            if let Err(err) = copy_to_clipboard(&text) {
+                print_failure("copy shader output to the clipboard", err);
+                return ExitCode::FAILURE;
+            }
+            eprintln!("Copied shader output to the clipboard");
+        } else if let Err(err) = write_output(
+            &output,
+            &opts.output_file,
+            &opts.variable_name,
+            trailer.as_deref(),
+            &style,
+            opts.header_parts,
+            opts.write_if_changed,
+        ) {
+            print_failure("write output file", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let (Some(object_file), Some(object)) = (&opts.object_file, &object) {
+        if let Err(err) = write_object(
+            object,
+            object_file,
+            opts.length_prefixed,
+            opts.write_if_changed,
+        ) {
+            print_failure("write object file", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(assembly_file) = &opts.assembly_file {
+        let _timer = timings::start(Phase::Write);
+        let written = disassemble(blob_bytes(&output))
+            .map_err(|err| err.to_string())
+            .and_then(|text| {
+                write_file(assembly_file, text.as_bytes(), opts.write_if_changed)
+                    .map_err(|err| err.to_string())
+            });
+        match written {
+            Ok(true) => eprintln!("Wrote the disassembly to {assembly_file}"),
+            Ok(false) => eprintln!("Disassembly in {assembly_file} is unchanged"),
+            Err(err) => {
+                print_failure("write the disassembly", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if let Some(rc_file) = &opts.rc_file {
+        if let Err(err) = write_rc(
+            blob_bytes(&output),
+            rc_file,
+            &opts.variable_name,
+            opts.object_file.as_deref(),
+        ) {
+            print_failure("write resource script", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(asm_inc_file) = &opts.asm_inc_file {
+        let _timer = timings::start(Phase::Write);
+        if let Err(err) = write_asm_inc(
+            blob_bytes(&output),
+            asm_inc_file,
+            &opts.variable_name,
+            opts.asm_flavor,
+            opts.write_if_changed,
+        ) {
+            print_failure("write assembler include", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(signature_file) = &opts.signature_file {
+        if let Err(err) = write_signature(
+            &output,
+            signature_file,
+            &opts.variable_name,
+            opts.with_output_signature,
+        ) {
+            print_failure("write shader signature", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(reflect_json_file) = &opts.reflect_json_file {
+        if let Err(err) = write_reflection_json(&output, reflect_json_file) {
+            print_failure("write shader reflection", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(meta_file) = &opts.meta_file {
+        if let Err(err) = write_meta(opts, &output, meta_file) {
+            print_failure("write compile metadata", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(descriptor_map_file) = &opts.descriptor_map_file {
+        if let Err(err) = write_descriptor_map(&output, descriptor_map_file) {
+            print_failure("write descriptor set mapping", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(pso_desc_file) = &opts.pso_desc_file {
+        let result = ShaderReflection::new(blob_bytes(&output))
+            .map_err(Box::<dyn std::error::Error>::from)
+            .and_then(|reflection| {
+                if !reflection.profile().starts_with("cs_") {
+                    print_warning(
+                        opts.warnings_to,
+                        &format!(
+                            "warning: --emit-pso-desc only applies to compute shaders, not {}",
+                            reflection.profile()
+                        ),
+                    );
+                    return Ok(());
+                }
+                write_pso_desc(&reflection, pso_desc_file, &opts.variable_name)
+            });
+        if let Err(err) = result {
+            print_failure("write compute pipeline description", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(bindings_hlsl_file) = &opts.bindings_hlsl_file {
+        if let Err(err) = write_bindings_hlsl(&output, bindings_hlsl_file) {
+            print_failure("write resource registers", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(dep_file) = &opts.dep_file {
+        if let Err(err) = write_dep_file(opts, &includes, dep_file) {
+            print_failure("write dependency file", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fxc2::args::parse_args;
+
+    fn parse_opt(args: &[&str]) -> ParseOpt {
+        let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let opts = parse_args(&args).ok().expect("the arguments parse");
+        ParseOpt::with_options(opts, args)
+    }
+
+    #[test]
+    fn header_array_layout() {
+        let style = ArrayStyle {
+            compact: false,
+            language: OutputLanguage::C,
+            byte_width: 3,
+            indent: "    ",
+        };
+        let mut text = Vec::new();
+        write_array(
+            &mut text,
+            &[0, 127, 128, 255, 1, 2, 64, 200],
+            "g_main",
+            &style,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "const BYTE g_main[] =\n{\n      0,127,128,255,  1,  2,\n     64,200\n};"
+        );
+    }
+
+    #[test]
+    fn preprocessed_line_directives() {
+        // as D3DPreprocess returns it, NUL-terminated
+        let preprocessed = b"#line 1 \"x.hlsl\"\nfloat4 main() : SV_Target  \n{\n  #line 4\n    return 1;\t\n}\n\n\n\0";
+        assert_eq!(
+            preprocessed_text(preprocessed, false),
+            "#line 1 \"x.hlsl\"\nfloat4 main() : SV_Target  \n{\n  #line 4\n    return 1;\t\n}\n\n\n"
+        );
+        assert_eq!(
+            preprocessed_text(preprocessed, true),
+            "float4 main() : SV_Target\n{\n    return 1;\n}\n"
+        );
+    }
+
+    #[test]
+    fn macros_in_command_line_order() {
+        let args = parse_opt(&[
+            "/T",
+            "ps_5_0",
+            "/Fh",
+            "a.h",
+            "/DZ=2",
+            "/DA=Z",
+            "/DZ=1",
+            "--sort-defines",
+            "x.hlsl",
+        ]);
+        let text = |text: PCSTR| (!text.is_null()).then(|| unsafe { text.to_string() }.unwrap());
+        let macros = args
+            .d3d_defines
+            .iter()
+            .map(|define| (text(define.Name), text(define.Definition)))
+            .collect::<Vec<_>>();
+        // first to last as given, even with --sort-defines, then the null terminator
+        let some = |name: &str, value: &str| (Some(name.to_owned()), Some(value.to_owned()));
+        assert_eq!(
+            macros,
+            [some("Z", "2"), some("A", "Z"), some("Z", "1"), (None, None)]
+        );
+    }
+
+    #[test]
+    fn technique_variable_names() {
+        let args = parse_opt(&["/Fh", "out/shader.h", "--techniques", "x.txt", "x.hlsl"]);
+        let techniques =
+            techniques::parse("VSMain vs_3_0\nPSMain ps_3_0\nCSMain cs_5_0\n").unwrap();
+        let names = technique_jobs(&args.opts, &techniques)
+            .iter()
+            .map(|job| {
+                let opts = parse_args(&job.args_for(&args)).ok().unwrap();
+                (opts.variable_name, opts.output_file)
+            })
+            .collect::<Vec<_>>();
+        // each from its own profile, unlike a single name for the whole run
+        let name = |name: &str, file: &str| (name.to_owned(), file.to_owned());
+        assert_eq!(
+            names,
+            [
+                name("g_vs30_VSMain", "out/shader.VSMain.h"),
+                name("g_ps30_PSMain", "out/shader.PSMain.h"),
+                name("g_CSMain", "out/shader.CSMain.h")
+            ]
+        );
+    }
+
+    #[test]
+    fn header_split_into_exactly_the_parts_asked_for() {
+        let data = (0..10).collect::<Vec<u8>>();
+        // chunks of div_ceil(10, 9) = 2 bytes would only make 5 parts
+        let lengths = |parts| {
+            split_evenly(&data, parts)
+                .map(<[u8]>::len)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lengths(4), [3, 3, 2, 2]);
+        assert_eq!(lengths(9), [2, 1, 1, 1, 1, 1, 1, 1, 1]);
+        assert_eq!(lengths(10), [1; 10]);
+        assert_eq!(lengths(1), [10]);
+        assert_eq!(split_evenly(&data, 6).collect::<Vec<_>>().concat(), data);
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn duplicate_macros_like_fxc() {
+        // fxc hands its /D macros to the compiler in the same order, which takes them like
+        // #define lines: the second Z replaces the first, and A expands to it
+        let args = parse_opt(&[
+            "/T", "ps_5_0", "/Fh", "a.h", "/DZ=2", "/DA=Z", "/DZ=1", "x.hlsl",
+        ]);
+        let include_handler =
+            IncludeHandler::new(Path::new("x.hlsl"), Vec::new(), true, Vec::new(), false);
+        let include = ID3DInclude::new(&include_handler);
+        let file_name = CString::new("x.hlsl").unwrap();
+        let profile = CString::new("ps_5_0").unwrap();
+        let output = compile::Call {
+            source: b"int a = A;\nint z = Z;\n",
+            file_name: &file_name,
+            defines: &args.d3d_defines,
+            include: &include,
+            include_name: "custom include handler",
+            entry_point: &args.opts.entry_point,
+            profile: &profile,
+            flags1: 0,
+            secondary_data_flags: 0,
+            secondary_data: &[],
+        }
+        .preprocess();
+        assert!(output.result.is_ok());
+        let text = preprocessed_text(blob_bytes(&output.data.unwrap()), true);
+        let lines = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, ["int a = 1;", "int z = 1;"]);
+    }
+
+    #[test]
+    fn initializer_never_empty() {
+        let end = |empty| {
+            let mut text = Vec::new();
+            end_initializer(&mut text, empty).unwrap();
+            String::from_utf8(text).unwrap()
+        };
+        assert_eq!(end(false), "};\n");
+        assert_eq!(end(true), "    { 0 },\n};\n");
+    }
 }
@@ -0,0 +1,55 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Byte order marks of shader sources. The compiler only reads 8-bit text, so sources saved as
+//! UTF-16 by an editor are transcoded to UTF-8 first, and a UTF-8 BOM is dropped.
+
+/// Returns the source as UTF-8 without a BOM. Sources without a BOM are returned unchanged.
+pub fn transcode(data: Vec<u8>) -> Vec<u8> {
+    let (units, big_endian) = match data.as_slice() {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => return rest.to_vec(),
+        [0xFF, 0xFE, rest @ ..] => (rest, false),
+        [0xFE, 0xFF, rest @ ..] => (rest, true),
+        _ => return data,
+    };
+    let units = units.chunks_exact(2).map(|pair| {
+        let pair = [pair[0], pair[1]];
+        if big_endian {
+            u16::from_be_bytes(pair)
+        } else {
+            u16::from_le_bytes(pair)
+        }
+    });
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect::<String>()
+        .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf16_sources() {
+        let little_endian = [
+            0xFF, 0xFE, b'f', 0, b'(', 0, 0xE9, 0, b')', 0, 0x3D, 0xD8, 0x00, 0xDE,
+        ];
+        assert_eq!(transcode(little_endian.to_vec()), "f(é)😀".as_bytes());
+        let big_endian = [0xFE, 0xFF, 0, b'f', 0, b'(', 0, 0xE9, 0, b')'];
+        assert_eq!(transcode(big_endian.to_vec()), "f(é)".as_bytes());
+        // a lone surrogate can't be transcoded, and an odd trailing byte is dropped
+        let broken = [0xFF, 0xFE, b'a', 0, 0x00, 0xD8, b'b', 0, b'c'];
+        assert_eq!(transcode(broken.to_vec()), "a\u{FFFD}b".as_bytes());
+    }
+
+    #[test]
+    fn utf8_sources() {
+        assert_eq!(transcode(b"\xEF\xBB\xBFfloat4 x;".to_vec()), b"float4 x;");
+        assert_eq!(transcode(b"float4 x;".to_vec()), b"float4 x;");
+        // only a BOM at the start counts
+        assert_eq!(transcode(b"x\xFF\xFE".to_vec()), b"x\xFF\xFE");
+        assert_eq!(transcode(Vec::new()), b"");
+    }
+}
@@ -0,0 +1,303 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Compiling HLSL from Rust, for tools and build scripts that would otherwise run the fxc2 binary.
+//!
+//! This covers a plain compile: the source, its defines and include directories, and the
+//! `D3DCOMPILE_*` flags. The binary's other options (outputs, stripping, checks) aren't part of it.
+//! The binary calls the compiler through the same [`Call`].
+
+use std::{
+    ffi::{c_void, CStr, CString},
+    fmt,
+    mem::MaybeUninit,
+    path::{Path, PathBuf},
+    slice,
+};
+
+use crate::{
+    bom, diagnostics,
+    include::IncludeHandler,
+    pragma,
+    timings::{self, Phase},
+    trace,
+};
+
+use windows::{
+    core::{HRESULT, PCSTR},
+    Win32::{
+        Foundation::{ERROR_FILE_NOT_FOUND, E_INVALIDARG},
+        Graphics::Direct3D::{
+            Fxc::{D3DCompile2, D3DPreprocess},
+            ID3DBlob, ID3DInclude, D3D_SHADER_MACRO,
+        },
+    },
+};
+
+/// What to compile and how.
+pub struct CompileOptions<'a> {
+    pub entry_point: &'a str,
+    /// The profile, e.g. `ps_5_0`
+    pub profile: &'a str,
+    /// The macros as (name, value), passed first to last like /D
+    pub defines: &'a [(&'a str, &'a str)],
+    /// Searched in order after the including file's directory and the source's, like /I
+    pub include_dirs: &'a [PathBuf],
+    /// `D3DCOMPILE_*` flags
+    pub flags: u32,
+}
+
+/// A compiled shader and the compiler's warnings.
+pub struct Compiled {
+    pub bytecode: Vec<u8>,
+    pub messages: String,
+}
+
+/// Why a compile failed: the compiler's error, and its messages.
+pub struct CompileError {
+    pub error: windows::core::Error,
+    pub messages: String,
+}
+
+impl Compiled {
+    /// The warnings in the compiler's messages.
+    pub fn diagnostics(&self) -> impl Iterator<Item = diagnostics::Diagnostic<'_>> {
+        diagnostics::parse(&self.messages)
+    }
+}
+
+impl CompileError {
+    /// The errors and warnings in the compiler's messages.
+    pub fn diagnostics(&self) -> impl Iterator<Item = diagnostics::Diagnostic<'_>> {
+        diagnostics::parse(&self.messages)
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.messages.trim_end() {
+            "" => write!(f, "{}", self.error),
+            messages => write!(f, "{}\n{messages}", self.error),
+        }
+    }
+}
+
+/// One call to the compiler, with everything it's given. [`compile_source`] makes it from
+/// [`CompileOptions`], the fxc2 binary from its command line.
+pub struct Call<'a> {
+    /// The source as handed to the compiler, see [`prepare_source`]
+    pub source: &'a [u8],
+    /// Names the source in messages
+    pub file_name: &'a CStr,
+    /// The macros, followed by an all-null terminator
+    pub defines: &'a [D3D_SHADER_MACRO],
+    pub include: &'a ID3DInclude,
+    /// Names the include handler in the trace
+    pub include_name: &'a str,
+    pub entry_point: &'a CStr,
+    pub profile: &'a CStr,
+    /// `D3DCOMPILE_*` flags
+    pub flags1: u32,
+    /// `D3DCOMPILE_SECONDARY_*` flags
+    pub secondary_data_flags: u32,
+    /// Empty for none
+    pub secondary_data: &'a [u8],
+}
+
+/// What the compiler returned.
+pub struct CallOutput {
+    pub result: windows::core::Result<()>,
+    /// The shader, or the preprocessed source. Only set on success.
+    pub data: Option<ID3DBlob>,
+    /// The compiler's errors and warnings, a NUL terminated string
+    pub errors: Option<ID3DBlob>,
+}
+
+impl Call<'_> {
+    /// Compiles with `D3DCompile2`.
+    pub fn compile(&self) -> CallOutput {
+        let _timer = timings::start(Phase::Compile);
+        let mut data: MaybeUninit<Option<ID3DBlob>> = MaybeUninit::uninit();
+        let mut errors: MaybeUninit<Option<ID3DBlob>> = MaybeUninit::uninit();
+        let hr = unsafe {
+            D3DCompile2(
+                self.source.as_ptr() as *const c_void,
+                self.source.len(),
+                PCSTR(self.file_name.as_ptr() as *const u8),
+                Some(self.defines.as_ptr()),
+                self.include,
+                PCSTR(self.entry_point.as_ptr() as *const u8),
+                PCSTR(self.profile.as_ptr() as *const u8),
+                self.flags1,
+                0,
+                self.secondary_data_flags,
+                (!self.secondary_data.is_empty())
+                    .then_some(self.secondary_data.as_ptr() as *const c_void),
+                self.secondary_data.len(),
+                data.as_mut_ptr(),
+                Some(errors.as_mut_ptr()),
+            )
+        };
+        trace::call(
+            "D3DCompile2",
+            format_args!(
+                "{} bytes, {:?}, {} defines, {}, {:?}, {:?}, flags1 0x{:08X}, \
+                 secondary data flags 0x{:X}, {} bytes of secondary data",
+                self.source.len(),
+                self.file_name,
+                self.defines.len().saturating_sub(1),
+                self.include_name,
+                self.entry_point,
+                self.profile,
+                self.flags1,
+                self.secondary_data_flags,
+                self.secondary_data.len()
+            ),
+            &hr,
+        );
+        CallOutput::new(hr, data, errors)
+    }
+
+    /// Only preprocesses with `D3DPreprocess`. The entry point, profile, flags and secondary data
+    /// aren't used.
+    pub fn preprocess(&self) -> CallOutput {
+        let _timer = timings::start(Phase::Preprocess);
+        let mut data: MaybeUninit<Option<ID3DBlob>> = MaybeUninit::uninit();
+        let mut errors: MaybeUninit<Option<ID3DBlob>> = MaybeUninit::uninit();
+        let hr = unsafe {
+            D3DPreprocess(
+                self.source.as_ptr() as *const c_void,
+                self.source.len(),
+                PCSTR(self.file_name.as_ptr() as *const u8),
+                Some(self.defines.as_ptr()),
+                self.include,
+                data.as_mut_ptr(),
+                Some(errors.as_mut_ptr()),
+            )
+        };
+        trace::call(
+            "D3DPreprocess",
+            format_args!(
+                "{} bytes, {:?}, {} defines, {}",
+                self.source.len(),
+                self.file_name,
+                self.defines.len().saturating_sub(1),
+                self.include_name
+            ),
+            &hr,
+        );
+        CallOutput::new(hr, data, errors)
+    }
+}
+
+impl CallOutput {
+    fn new(
+        result: windows::core::Result<()>,
+        data: MaybeUninit<Option<ID3DBlob>>,
+        errors: MaybeUninit<Option<ID3DBlob>>,
+    ) -> CallOutput {
+        // both are written whatever the result
+        let (data, errors) = unsafe { (data.assume_init(), errors.assume_init()) };
+        CallOutput {
+            data: data.filter(|_| result.is_ok()),
+            result,
+            errors,
+        }
+    }
+}
+
+/// The source as the compiler gets it: a byte order mark is handled and `#pragma fxc_flags` lines
+/// are blanked.
+pub fn prepare_source(source: Vec<u8>) -> Vec<u8> {
+    let mut source = bom::transcode(source);
+    pragma::blank(&mut source);
+    source
+}
+
+/// Reads and compiles the file. Includes are resolved relative to it first.
+pub fn compile_file(path: &Path, options: &CompileOptions) -> Result<Compiled, CompileError> {
+    let source = std::fs::read(path).map_err(|err| {
+        let code = err
+            .raw_os_error()
+            .map_or(ERROR_FILE_NOT_FOUND.0, |code| code as u32);
+        CompileError {
+            error: HRESULT::from_win32(code).into(),
+            messages: format!("{}: {err}", path.display()),
+        }
+    })?;
+    compile_source(&source, path, options)
+}
+
+/// Compiles the source, named `path` in messages and resolving includes relative to it first. Like
+/// the binary, a byte order mark is handled and `#pragma fxc_flags` lines are blanked, but their
+/// flags aren't applied: `options.flags` are used as given.
+pub fn compile_source(
+    source: &[u8],
+    path: &Path,
+    options: &CompileOptions,
+) -> Result<Compiled, CompileError> {
+    let invalid = |what: &str| CompileError {
+        error: E_INVALIDARG.into(),
+        messages: format!("{what} contains a NUL byte"),
+    };
+    let source = prepare_source(source.to_vec());
+    let file_name =
+        CString::new(path.to_string_lossy().as_bytes()).map_err(|_| invalid("the path"))?;
+    let entry_point = CString::new(options.entry_point).map_err(|_| invalid("the entry point"))?;
+    let profile =
+        CString::new(options.profile.to_ascii_lowercase()).map_err(|_| invalid("the profile"))?;
+    let defines = options
+        .defines
+        .iter()
+        .map(|(name, value)| Ok((CString::new(*name)?, CString::new(*value)?)))
+        .collect::<Result<Vec<_>, std::ffi::NulError>>()
+        .map_err(|_| invalid("a define"))?;
+    // points into defines
+    let mut d3d_defines = defines
+        .iter()
+        .map(|(name, value)| D3D_SHADER_MACRO {
+            Name: PCSTR(name.as_ptr() as *const u8),
+            Definition: PCSTR(value.as_ptr() as *const u8),
+        })
+        .collect::<Vec<_>>();
+    d3d_defines.push(D3D_SHADER_MACRO::default()); // null terminator
+
+    let include_handler =
+        IncludeHandler::new(path, options.include_dirs.to_vec(), true, Vec::new(), false);
+    let include = ID3DInclude::new(&include_handler);
+
+    let output = Call {
+        source: &source,
+        file_name: &file_name,
+        defines: &d3d_defines,
+        include: &include,
+        include_name: "custom include handler",
+        entry_point: &entry_point,
+        profile: &profile,
+        flags1: options.flags,
+        secondary_data_flags: 0,
+        secondary_data: &[],
+    }
+    .compile();
+    let messages = output
+        .errors
+        .map(|errors| {
+            String::from_utf8_lossy(blob_bytes(&errors))
+                .trim_end_matches('\0')
+                .to_owned()
+        })
+        .unwrap_or_default();
+    if let Err(error) = output.result {
+        return Err(CompileError { error, messages });
+    }
+    let data = output.data.expect("D3DCompile2 succeeded without a shader");
+    Ok(Compiled {
+        bytecode: blob_bytes(&data).to_vec(),
+        messages,
+    })
+}
+
+fn blob_bytes(blob: &ID3DBlob) -> &[u8] {
+    unsafe { slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize()) }
+}
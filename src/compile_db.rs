@@ -0,0 +1,74 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Lookup of recorded compile arguments in a database modeled on clang's `compile_commands.json`.
+//!
+//! The database is a JSON array of entries:
+//!
+//! ```json
+//! [
+//!   {
+//!     "directory": "/project/build",
+//!     "file": "../shaders/blur.hlsl",
+//!     "arguments": ["fxc2", "/T", "ps_5_0", "/E", "main", "/Fh", "blur.h", "../shaders/blur.hlsl"]
+//!   }
+//! ]
+//! ```
+//!
+//! The first argument is the compiler and is skipped. `file` and the arguments are relative to
+//! `directory`, which is itself relative to the database and defaults to the database's directory.
+
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+pub struct Entry {
+    pub directory: PathBuf,
+    /// The arguments without the compiler
+    pub arguments: Vec<String>,
+}
+
+/// Finds the entry for `file` in the database text `db`, read from `db_dir`.
+/// Returns a description of the problem if the database is malformed.
+pub fn find(db: &str, db_dir: &Path, file: &Path) -> Result<Option<Entry>, String> {
+    fn normalize(path: &Path) -> PathBuf {
+        path.canonicalize().unwrap_or_else(|_| path.to_owned())
+    }
+
+    let db: Value = serde_json::from_str(db).map_err(|err| err.to_string())?;
+    let entries = db.as_array().ok_or("expected an array of entries")?;
+    let file = normalize(file);
+    for entry in entries {
+        let field = |name: &str| {
+            entry
+                .get(name)
+                .ok_or_else(|| format!("entry is missing '{name}'"))
+        };
+        let directory = match entry.get("directory") {
+            Some(directory) => db_dir.join(directory.as_str().ok_or("'directory' isn't a string")?),
+            None => db_dir.to_owned(),
+        };
+        let entry_file = field("file")?.as_str().ok_or("'file' isn't a string")?;
+        if normalize(&directory.join(entry_file)) != file {
+            continue;
+        }
+        let arguments = field("arguments")?
+            .as_array()
+            .ok_or("'arguments' isn't an array")?
+            .iter()
+            .skip(1)
+            .map(|argument| {
+                argument
+                    .as_str()
+                    .map(str::to_owned)
+                    .ok_or("'arguments' has a non-string element")
+            })
+            .collect::<Result<_, _>>()?;
+        return Ok(Some(Entry {
+            directory,
+            arguments,
+        }));
+    }
+    Ok(None)
+}
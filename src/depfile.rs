@@ -0,0 +1,99 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Make-style dependency files (`-MF`), listing the files a compile read.
+
+use std::path::{self, Component, Path, PathBuf};
+
+/// Rewrites `path` relative to `base`. Paths on another drive or root are returned absolute.
+pub fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let (Ok(path), Ok(base)) = (path::absolute(path), path::absolute(base)) else {
+        return path.to_owned();
+    };
+    let path = path.components().collect::<Vec<_>>();
+    let base = base.components().collect::<Vec<_>>();
+    let common = path
+        .iter()
+        .zip(base.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if !matches!(
+        path.get(..common),
+        Some([.., Component::RootDir | Component::Normal(_)])
+    ) {
+        return path.iter().collect();
+    }
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    relative.extend(&path[common..]);
+    relative
+}
+
+/// Writes a path the way make reads it: `/` separators, spaces and `$` escaped.
+fn escape(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            ' ' | '#' => escaped.push('\\'),
+            '$' => escaped.push('$'),
+            _ => {}
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Builds the rule `target: dependencies...`, one dependency per line. With `relative_to` set, the
+/// dependencies are written relative to that directory instead of as they were resolved.
+pub fn make_rule(target: &str, dependencies: &[PathBuf], relative_to: Option<&Path>) -> String {
+    let mut rule = escape(Path::new(target));
+    rule.push(':');
+    for dependency in dependencies {
+        let dependency = match relative_to {
+            Some(base) => self::relative_to(dependency, base),
+            None => dependency.clone(),
+        };
+        rule.push_str(" \\\n  ");
+        rule.push_str(&escape(&dependency));
+    }
+    rule.push('\n');
+    rule
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rule() {
+        let dependencies = [
+            PathBuf::from("shaders/main.hlsl"),
+            PathBuf::from("shaders/my include.hlsli"),
+            PathBuf::from("shaders\\win.hlsli"),
+            PathBuf::from("$(dir)/#1.hlsli"),
+        ];
+        assert_eq!(
+            make_rule("out/main.cso", &dependencies, None),
+            "out/main.cso: \\\n  shaders/main.hlsl \\\n  shaders/my\\ include.hlsli \\\n  shaders/win.hlsli \\\n  $$(dir)/\\#1.hlsli\n"
+        );
+        assert_eq!(make_rule("out.h", &[], None), "out.h:\n");
+    }
+
+    #[test]
+    fn relative_dependencies() {
+        let dependencies = [
+            PathBuf::from("/project/shaders/main.hlsl"),
+            PathBuf::from("/project/shaders/common/light.hlsli"),
+            PathBuf::from("/project/include/defs.hlsli"),
+            PathBuf::from("/elsewhere/sdk.hlsli"),
+        ];
+        assert_eq!(
+            make_rule("main.cso", &dependencies, Some(Path::new("/project/shaders"))),
+            "main.cso: \\\n  main.hlsl \\\n  common/light.hlsli \\\n  ../include/defs.hlsli \\\n  ../../elsewhere/sdk.hlsli\n"
+        );
+    }
+}
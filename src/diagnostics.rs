@@ -0,0 +1,119 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Parsing of the compiler's diagnostic lines, e.g.
+//! `shader.hlsl(12,5-20): warning X3206: implicit truncation of vector type`.
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+pub struct Diagnostic<'a> {
+    /// `file(line,column)`, missing for diagnostics about the whole compile
+    pub location: Option<&'a str>,
+    pub severity: Severity,
+    /// `X3206`
+    pub code: &'a str,
+    pub message: &'a str,
+}
+
+/// Parses one line of the compiler's messages. Returns None for lines that aren't a diagnostic,
+/// like the continuation lines of a multi-line message.
+pub fn parse_line(line: &str) -> Option<Diagnostic<'_>> {
+    let line = line.trim_end();
+    for (keyword, severity) in [("error", Severity::Error), ("warning", Severity::Warning)] {
+        let (location, rest) = if let Some(rest) = line.strip_prefix(&format!("{keyword} ")) {
+            (None, rest)
+        } else if let Some(at) = line.find(&format!(": {keyword} ")) {
+            (Some(&line[..at]), &line[at + 2 + keyword.len()..])
+        } else {
+            continue;
+        };
+        let Some((code, message)) = rest.trim_start().split_once(": ") else {
+            continue;
+        };
+        if code.is_empty() || code.contains(char::is_whitespace) {
+            continue;
+        }
+        return Some(Diagnostic {
+            location,
+            severity,
+            code,
+            message,
+        });
+    }
+    None
+}
+
+/// Parses every diagnostic line of the compiler's messages.
+pub fn parse(text: &str) -> impl Iterator<Item = Diagnostic<'_>> {
+    text.lines().filter_map(parse_line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(diagnostic: Diagnostic<'_>) -> (Option<&str>, bool, &str, &str) {
+        (
+            diagnostic.location,
+            diagnostic.severity == Severity::Warning,
+            diagnostic.code,
+            diagnostic.message,
+        )
+    }
+
+    #[test]
+    fn diagnostic_lines() {
+        let line =
+            "C:\\shaders\\x.hlsl(12,5-20): warning X3206: implicit truncation of vector type";
+        assert_eq!(
+            parse_line(line).map(fields),
+            Some((
+                Some("C:\\shaders\\x.hlsl(12,5-20)"),
+                true,
+                "X3206",
+                "implicit truncation of vector type"
+            ))
+        );
+        assert_eq!(
+            parse_line("x.hlsl(3,1): error X3000: syntax error: unexpected token 'float'\r")
+                .map(fields),
+            Some((
+                Some("x.hlsl(3,1)"),
+                false,
+                "X3000",
+                "syntax error: unexpected token 'float'"
+            ))
+        );
+        assert_eq!(
+            parse_line("error X3501: 'main': entrypoint not found").map(fields),
+            Some((None, false, "X3501", "'main': entrypoint not found"))
+        );
+    }
+
+    #[test]
+    fn other_lines() {
+        assert!(parse_line("").is_none());
+        assert!(parse_line("    float4 main() : SV_Target").is_none());
+        assert!(parse_line("compilation failed; no code produced").is_none());
+        // `error` inside a message isn't a diagnostic
+        assert!(parse_line("x.hlsl(1,1): note: see the previous error here").is_none());
+        assert!(parse_line("warnings: none").is_none());
+    }
+
+    #[test]
+    fn messages() {
+        let messages = "x.hlsl(2,3): warning X3206: truncation\n\
+                        x.hlsl(4,1): error X3004: undeclared identifier 'y'\n\
+                        \x20   in the expansion of a macro\n\
+                        x.hlsl(5,1): warning X3557: loop only executes for 1 iteration\n";
+        let codes = parse(messages)
+            .map(|diagnostic| diagnostic.code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes, ["X3206", "X3004", "X3557"]);
+    }
+}
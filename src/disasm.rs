@@ -0,0 +1,68 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Helpers for working with the text D3DDisassemble produces.
+
+/// The instruction lines of a disassembly, without comments and blank lines.
+pub fn instructions(disassembly: &str) -> Vec<&str> {
+    disassembly
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("//"))
+        .collect()
+}
+
+/// The instruction without its precision modifiers: `_pp` suffixes (shader model 2 and 3) and
+/// `{min16f}`-style minimum precision annotations (shader model 4 and up).
+pub fn strip_precision(instruction: &str) -> String {
+    let mut stripped = String::with_capacity(instruction.len());
+    let mut rest = instruction;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("_pp") {
+            if !after.starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+                rest = after;
+                continue;
+            }
+        }
+        if rest.starts_with("{min") {
+            if let Some(end) = rest.find('}') {
+                stripped.truncate(stripped.trim_end().len());
+                rest = &rest[end + 1..];
+                continue;
+            }
+        }
+        let c = rest.chars().next().unwrap();
+        stripped.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// An instruction that differs between two disassemblies.
+pub struct Difference {
+    /// Index among the instructions
+    pub index: usize,
+    pub first: String,
+    pub second: String,
+    /// True if only the precision modifiers differ
+    pub precision_only: bool,
+}
+
+/// Compares two disassemblies instruction by instruction.
+pub fn compare(first: &str, second: &str) -> Vec<Difference> {
+    let first = instructions(first);
+    let second = instructions(second);
+    (0..first.len().max(second.len()))
+        .filter_map(|index| {
+            let a = first.get(index).copied().unwrap_or_default();
+            let b = second.get(index).copied().unwrap_or_default();
+            (a != b).then(|| Difference {
+                index,
+                first: a.to_owned(),
+                second: b.to_owned(),
+                precision_only: strip_precision(a) == strip_precision(b),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,526 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Helpers for the DXBC container format that compiled shaders are stored in.
+//!
+//! The container starts with a 32 byte header:
+//! - `DXBC` magic
+//! - 16 byte checksum of everything after it
+//! - version (always 1)
+//! - total size in bytes
+//! - number of chunks
+//!
+//! followed by one u32 offset per chunk. Each chunk (part) is a fourcc and a u32 size followed by
+//! its data. All values are little-endian.
+
+pub const MAGIC: &[u8; 4] = b"DXBC";
+pub const ROOT_SIGNATURE: &[u8; 4] = b"RTS0";
+const CHECKSUM_RANGE: std::ops::Range<usize> = 4..20;
+const HEADER_SIZE: usize = 32;
+
+/// A chunk of a container.
+pub struct Part<'a> {
+    pub fourcc: [u8; 4],
+    pub data: &'a [u8],
+}
+
+/// Returns true if the data starts like a DXBC container.
+pub fn is_container(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Splits a container into its parts. Returns None if the data isn't a valid container.
+pub fn parts(data: &[u8]) -> Option<Vec<Part<'_>>> {
+    if !is_container(data) {
+        return None;
+    }
+    let count = read_u32(data, 28)? as usize;
+    (0..count)
+        .map(|i| {
+            let offset = read_u32(data, HEADER_SIZE + i * 4)? as usize;
+            let fourcc = data.get(offset..offset.checked_add(4)?)?.try_into().ok()?;
+            let size = read_u32(data, offset + 4)? as usize;
+            let start = offset + 8;
+            let data = data.get(start..start.checked_add(size)?)?;
+            Some(Part { fourcc, data })
+        })
+        .collect()
+}
+
+/// Where a chunk sits in a container.
+pub struct Section {
+    pub fourcc: [u8; 4],
+    /// The offset of the chunk (its fourcc) from the start of the container
+    pub offset: usize,
+    /// The size of the chunk's data, not counting the fourcc and size before it
+    pub size: usize,
+}
+
+/// The chunks of a container as its header describes them, whether or not they make sense.
+pub struct Layout {
+    /// The total size stored in the header
+    pub claimed_size: usize,
+    pub sections: Vec<Section>,
+    /// Everything inconsistent with the header or the data
+    pub problems: Vec<String>,
+}
+
+/// Reads the layout of a container, checking it along the way. Unlike `parts`, this keeps going
+/// when something is off. Returns None if the data isn't a container or its header is cut off.
+pub fn layout(data: &[u8]) -> Option<Layout> {
+    if !is_container(data) {
+        return None;
+    }
+    let claimed_size = read_u32(data, 24)? as usize;
+    let count = read_u32(data, 28)? as usize;
+    let mut problems = Vec::new();
+    if claimed_size != data.len() {
+        problems.push(format!(
+            "the header claims {claimed_size} bytes, the container has {}",
+            data.len()
+        ));
+    }
+
+    let mut sections = Vec::new();
+    for i in 0..count {
+        let Some(offset) = read_u32(data, HEADER_SIZE + i * 4) else {
+            problems.push(format!(
+                "the offsets of chunks {i} to {} are cut off",
+                count - 1
+            ));
+            break;
+        };
+        let offset = offset as usize;
+        let (Some(fourcc), Some(size)) = (
+            data.get(offset..offset.saturating_add(4)),
+            read_u32(data, offset.saturating_add(4)),
+        ) else {
+            problems.push(format!("chunk {i} at offset {offset} is past the end"));
+            continue;
+        };
+        let fourcc: [u8; 4] = fourcc.try_into().unwrap();
+        let size = size as usize;
+        if offset < HEADER_SIZE + count * 4 {
+            problems.push(format!("chunk {i} at offset {offset} overlaps the header"));
+        }
+        if offset + 8 + size > data.len().min(claimed_size) {
+            problems.push(format!(
+                "chunk {i} ({}) at offset {offset} with {size} bytes runs past the end",
+                String::from_utf8_lossy(&fourcc)
+            ));
+        }
+        sections.push(Section {
+            fourcc,
+            offset,
+            size,
+        });
+    }
+
+    let mut sorted = sections.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|section| section.offset);
+    for pair in sorted.windows(2) {
+        if pair[0].offset + 8 + pair[0].size > pair[1].offset {
+            problems.push(format!(
+                "chunks {} at offset {} and {} at offset {} overlap",
+                String::from_utf8_lossy(&pair[0].fourcc),
+                pair[0].offset,
+                String::from_utf8_lossy(&pair[1].fourcc),
+                pair[1].offset
+            ));
+        }
+    }
+
+    Some(Layout {
+        claimed_size,
+        sections,
+        problems,
+    })
+}
+
+/// Assembles a container from its parts, including the checksum.
+pub fn build(parts: &[Part]) -> Vec<u8> {
+    let mut offset = HEADER_SIZE + parts.len() * 4;
+    let mut offsets = Vec::with_capacity(parts.len());
+    for part in parts {
+        offsets.push(offset as u32);
+        offset += 8 + part.data.len();
+    }
+
+    let mut data = Vec::with_capacity(offset);
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&[0; 16]);
+    data.extend_from_slice(&1u32.to_le_bytes());
+    data.extend_from_slice(&(offset as u32).to_le_bytes());
+    data.extend_from_slice(&(parts.len() as u32).to_le_bytes());
+    for offset in offsets {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    for part in parts {
+        data.extend_from_slice(&part.fourcc);
+        data.extend_from_slice(&(part.data.len() as u32).to_le_bytes());
+        data.extend_from_slice(part.data);
+    }
+    update_checksum(&mut data);
+    data
+}
+
+/// Removes every part with the given fourcc and re-signs the container.
+/// Returns None if the data isn't a valid container.
+pub fn remove_part(data: &[u8], fourcc: &[u8; 4]) -> Option<Vec<u8>> {
+    let parts = parts(data)?
+        .into_iter()
+        .filter(|part| part.fourcc != *fourcc)
+        .collect::<Vec<_>>();
+    Some(build(&parts))
+}
+
+/// The parts that change between builds of the same shader without changing the compiled code:
+/// debug information (which embeds the source paths and a timestamp) and private data.
+pub const VOLATILE_PARTS: &[[u8; 4]] = &[*b"SDBG", *b"SPDB", *b"ILDB", *b"ILDN", *b"PRIV"];
+
+/// Compares two containers part by part, skipping the `ignored` parts, and describes every part
+/// that differs. Parts are matched by fourcc, and by position among the parts sharing it.
+/// Returns None if either isn't a valid container.
+pub fn differing_parts(expected: &[u8], actual: &[u8], ignored: &[[u8; 4]]) -> Option<Vec<String>> {
+    // each part with its position among the parts sharing its fourcc
+    fn numbered<'a>(parts: Vec<Part<'a>>, ignored: &[[u8; 4]]) -> Vec<(usize, Part<'a>)> {
+        let mut numbered: Vec<(usize, Part)> = Vec::new();
+        for part in parts
+            .into_iter()
+            .filter(|part| !ignored.contains(&part.fourcc))
+        {
+            let nth = numbered
+                .iter()
+                .filter(|(_, other)| other.fourcc == part.fourcc)
+                .count();
+            numbered.push((nth, part));
+        }
+        numbered
+    }
+    fn find<'a>(parts: &[(usize, Part<'a>)], nth: usize, fourcc: [u8; 4]) -> Option<&'a [u8]> {
+        parts
+            .iter()
+            .find(|(other_nth, other)| *other_nth == nth && other.fourcc == fourcc)
+            .map(|(_, part)| part.data)
+    }
+    let expected = numbered(parts(expected)?, ignored);
+    let actual = numbered(parts(actual)?, ignored);
+
+    let mut differences = Vec::new();
+    for (nth, part) in expected.iter() {
+        let name = String::from_utf8_lossy(&part.fourcc);
+        match find(&actual, *nth, part.fourcc) {
+            None => differences.push(format!("{name} is missing")),
+            Some(data) if data.len() != part.data.len() => differences.push(format!(
+                "{name} is {} bytes, expected {} bytes",
+                data.len(),
+                part.data.len()
+            )),
+            Some(data) if data != part.data => {
+                let offset = data.iter().zip(part.data).position(|(a, b)| a != b);
+                differences.push(format!(
+                    "{name} differs from byte {}",
+                    offset.unwrap_or_default()
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+    for (nth, part) in actual.iter() {
+        if find(&expected, *nth, part.fourcc).is_none() {
+            let name = String::from_utf8_lossy(&part.fourcc);
+            differences.push(format!("{name} is unexpected"));
+        }
+    }
+    Some(differences)
+}
+
+/// The checksum stored in the container header, which drivers and debuggers report as the
+/// shader hash. Returns None if the data isn't a container.
+pub fn stored_checksum(data: &[u8]) -> Option<[u8; 16]> {
+    if !is_container(data) {
+        return None;
+    }
+    data.get(CHECKSUM_RANGE)?.try_into().ok()
+}
+
+/// Computes the checksum stored in the container header.
+///
+/// This is MD5 over everything after the checksum, except that the message length is stored at
+/// the start of the final block (with an extra value at its end) instead of standard MD5 padding,
+/// and the raw state is used as the digest.
+pub fn checksum(data: &[u8]) -> [u8; 16] {
+    let data = &data[CHECKSUM_RANGE.end..];
+    let num_bits = (data.len() as u32).wrapping_mul(8);
+    let num_bits_part2 = (num_bits >> 2) | 1;
+
+    let mut state = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    let mut chunks = data.chunks_exact(64);
+    for chunk in &mut chunks {
+        md5_transform(&mut state, chunk);
+    }
+    let leftover = chunks.remainder();
+
+    let mut block = [0u8; 64];
+    if leftover.len() >= 56 {
+        block[..leftover.len()].copy_from_slice(leftover);
+        block[leftover.len()] = 0x80;
+        md5_transform(&mut state, &block);
+
+        block = [0u8; 64];
+        block[..4].copy_from_slice(&num_bits.to_le_bytes());
+        block[60..].copy_from_slice(&num_bits_part2.to_le_bytes());
+        md5_transform(&mut state, &block);
+    } else {
+        block[..4].copy_from_slice(&num_bits.to_le_bytes());
+        block[4..4 + leftover.len()].copy_from_slice(leftover);
+        block[4 + leftover.len()] = 0x80;
+        block[60..].copy_from_slice(&num_bits_part2.to_le_bytes());
+        md5_transform(&mut state, &block);
+    }
+
+    let mut digest = [0u8; 16];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+/// Standard MD5 of the data, for fingerprinting files.
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    let mut state = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    let mut chunks = data.chunks_exact(64);
+    for chunk in &mut chunks {
+        md5_transform(&mut state, chunk);
+    }
+    let leftover = chunks.remainder();
+
+    let mut padded = [0u8; 128];
+    padded[..leftover.len()].copy_from_slice(leftover);
+    padded[leftover.len()] = 0x80;
+    let len = if leftover.len() >= 56 { 128 } else { 64 };
+    let num_bits = (data.len() as u64).wrapping_mul(8);
+    padded[len - 8..len].copy_from_slice(&num_bits.to_le_bytes());
+    for block in padded[..len].chunks_exact(64) {
+        md5_transform(&mut state, block);
+    }
+
+    let mut digest = [0u8; 16];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+/// Recomputes the checksum of a container after its contents were modified.
+/// Does nothing if the data isn't a DXBC container.
+pub fn update_checksum(data: &mut [u8]) {
+    if !is_container(data) || data.len() < CHECKSUM_RANGE.end {
+        return;
+    }
+    let digest = checksum(data);
+    data[CHECKSUM_RANGE].copy_from_slice(&digest);
+}
+
+fn md5_transform(state: &mut [u32; 4], block: &[u8]) {
+    const SHIFTS: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+    const SINES: [u32; 64] = [
+        0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613,
+        0xfd469501, 0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193,
+        0xa679438e, 0x49b40821, 0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d,
+        0x02441453, 0xd8a1e681, 0xe7d3fbc8, 0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed,
+        0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a, 0xfffa3942, 0x8771f681, 0x6d9d6122,
+        0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70, 0x289b7ec6, 0xeaa127fa,
+        0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665, 0xf4292244,
+        0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+        0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb,
+        0xeb86d391,
+    ];
+
+    let mut words = [0u32; 16];
+    for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+
+    let [mut a, mut b, mut c, mut d] = *state;
+    for i in 0..64 {
+        let (f, g) = match i / 16 {
+            0 => ((b & c) | (!b & d), i),
+            1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+            2 => (b ^ c ^ d, (3 * i + 5) % 16),
+            _ => (c ^ (b | !d), (7 * i) % 16),
+        };
+        let f = f
+            .wrapping_add(a)
+            .wrapping_add(SINES[i])
+            .wrapping_add(words[g]);
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(f.rotate_left(SHIFTS[i]));
+    }
+    state[0] = state[0].wrapping_add(a);
+    state[1] = state[1].wrapping_add(b);
+    state[2] = state[2].wrapping_add(c);
+    state[3] = state[3].wrapping_add(d);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fourccs(data: &[u8]) -> Vec<[u8; 4]> {
+        parts(data)
+            .expect("a valid container")
+            .iter()
+            .map(|part| part.fourcc)
+            .collect()
+    }
+
+    fn is_signed(data: &[u8]) -> bool {
+        stored_checksum(data) == Some(checksum(data))
+    }
+
+    #[test]
+    fn build_and_split() {
+        let data = build(&[
+            Part {
+                fourcc: *b"SHEX",
+                data: &[1, 2, 3, 4],
+            },
+            Part {
+                fourcc: *b"STAT",
+                data: &[5; 9],
+            },
+        ]);
+        // header, two offsets, then each part's fourcc and size before its data
+        assert_eq!(data.len(), 32 + 2 * 4 + (8 + 4) + (8 + 9));
+        assert_eq!(read_u32(&data, 24), Some(data.len() as u32));
+        assert_eq!(read_u32(&data, 28), Some(2));
+        assert_eq!(read_u32(&data, 32), Some(40));
+        assert_eq!(read_u32(&data, 36), Some(52));
+        let parts = parts(&data).unwrap();
+        assert_eq!(parts[0].data, [1, 2, 3, 4]);
+        assert_eq!(parts[1].data, [5; 9]);
+        assert!(is_signed(&data));
+        assert!(layout(&data).unwrap().problems.is_empty());
+    }
+
+    #[test]
+    fn remove_root_signature() {
+        let data = build(&[
+            Part {
+                fourcc: *b"SHEX",
+                data: &[1, 2, 3, 4],
+            },
+            Part {
+                fourcc: *ROOT_SIGNATURE,
+                data: &[2, 0, 0, 0, 0, 0, 0, 0],
+            },
+            Part {
+                fourcc: *b"STAT",
+                data: &[5; 9],
+            },
+        ]);
+        let stripped = remove_part(&data, ROOT_SIGNATURE).unwrap();
+        assert_eq!(fourccs(&stripped), [*b"SHEX", *b"STAT"]);
+        assert_eq!(read_u32(&stripped, 24), Some(stripped.len() as u32));
+        assert!(is_signed(&stripped));
+        assert_ne!(stored_checksum(&stripped), stored_checksum(&data));
+        // nothing to remove leaves the same container
+        assert_eq!(
+            remove_part(&stripped, ROOT_SIGNATURE),
+            Some(stripped.clone())
+        );
+        assert_eq!(remove_part(b"not a container", ROOT_SIGNATURE), None);
+    }
+
+    #[test]
+    fn checksum_covers_everything_after_it() {
+        let mut data = build(&[Part {
+            fourcc: *b"SHEX",
+            data: &[0; 100],
+        }]);
+        let signed = stored_checksum(&data).unwrap();
+        // the checksum doesn't cover itself
+        data[4] ^= 1;
+        assert_eq!(checksum(&data), signed);
+        data[4] ^= 1;
+        // but anything after it, the header included
+        data[24] ^= 1;
+        assert_ne!(checksum(&data), signed);
+        data[24] ^= 1;
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert_ne!(checksum(&data), signed);
+        update_checksum(&mut data);
+        assert!(is_signed(&data));
+    }
+
+    #[test]
+    fn stripped_container_stays_valid() {
+        let data = build(&[
+            Part {
+                fourcc: *b"RDEF",
+                data: &[7; 28],
+            },
+            Part {
+                fourcc: *b"SHEX",
+                data: &[1, 2, 3, 4],
+            },
+            Part {
+                fourcc: *b"SDBG",
+                data: &[9; 70],
+            },
+            Part {
+                fourcc: *b"PRIV",
+                data: b"notes",
+            },
+        ]);
+        let mut stripped = data;
+        for fourcc in [b"RDEF", b"SDBG", b"PRIV"] {
+            stripped = remove_part(&stripped, fourcc).unwrap();
+        }
+        assert_eq!(fourccs(&stripped), [*b"SHEX"]);
+        assert_eq!(parts(&stripped).unwrap()[0].data, [1, 2, 3, 4]);
+        let layout = layout(&stripped).unwrap();
+        assert_eq!(layout.claimed_size, stripped.len());
+        assert!(layout.problems.is_empty());
+        assert!(is_signed(&stripped));
+    }
+
+    fn hex(digest: [u8; 16]) -> String {
+        digest.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn md5_vectors() {
+        // from RFC 1321, covering both padding cases and several blocks
+        assert_eq!(hex(md5(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(hex(md5(b"abc")), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            hex(md5(b"abcdefghijklmnopqrstuvwxyz")),
+            "c3fcd3d76192e4007dfb496cca67e13b"
+        );
+        assert_eq!(
+            hex(md5(
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789"
+            )),
+            "d174ab98d277d9f5a5611c2c9f419d9f"
+        );
+        assert_eq!(
+            hex(md5("1234567890".repeat(8).as_bytes())),
+            "57edf4a22be3c955ac49da2e2107b67a"
+        );
+    }
+}
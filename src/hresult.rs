@@ -0,0 +1,66 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use windows::{
+    core::HRESULT,
+    Win32::Foundation::{
+        ERROR_FILE_NOT_FOUND, ERROR_PATH_NOT_FOUND, E_ACCESSDENIED, E_FAIL, E_INVALIDARG,
+        E_NOTIMPL, E_OUTOFMEMORY, E_POINTER, S_FALSE, S_OK,
+    },
+};
+
+/// The symbolic name of the HRESULTs the compiler APIs commonly return.
+pub fn name(code: HRESULT) -> Option<&'static str> {
+    let table = [
+        (S_OK, "S_OK"),
+        (S_FALSE, "S_FALSE"),
+        (E_FAIL, "E_FAIL"),
+        (E_INVALIDARG, "E_INVALIDARG"),
+        (E_OUTOFMEMORY, "E_OUTOFMEMORY"),
+        (E_NOTIMPL, "E_NOTIMPL"),
+        (E_POINTER, "E_POINTER"),
+        (E_ACCESSDENIED, "E_ACCESSDENIED"),
+        (ERROR_FILE_NOT_FOUND.to_hresult(), "ERROR_FILE_NOT_FOUND"),
+        (ERROR_PATH_NOT_FOUND.to_hresult(), "ERROR_PATH_NOT_FOUND"),
+    ];
+    table
+        .iter()
+        .find(|(hr, _)| *hr == code)
+        .map(|(_, name)| *name)
+}
+
+/// A plain-English explanation of the HRESULTs D3DCompile commonly fails with.
+pub fn explain(code: HRESULT) -> Option<&'static str> {
+    let table = [
+        (
+            ERROR_FILE_NOT_FOUND.to_hresult(),
+            "The input or an included file was not found.",
+        ),
+        (
+            ERROR_PATH_NOT_FOUND.to_hresult(),
+            "The directory of the input or an included file was not found.",
+        ),
+        (
+            E_ACCESSDENIED,
+            "The input or an included file couldn't be opened, check its permissions.",
+        ),
+        (
+            E_INVALIDARG,
+            "The compiler rejected an argument, check the profile (/T) and the entry point (/E).",
+        ),
+        (
+            E_FAIL,
+            "The shader failed to compile, the compiler's messages above say why.",
+        ),
+        (E_OUTOFMEMORY, "The compiler ran out of memory."),
+        (
+            E_NOTIMPL,
+            "The installed d3dcompiler doesn't support this, it may be too old for the profile.",
+        ),
+    ];
+    table
+        .iter()
+        .find(|(hr, _)| *hr == code)
+        .map(|(_, explanation)| *explanation)
+}
@@ -0,0 +1,222 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::c_void,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{bom, trace};
+
+use windows::{
+    core::{HRESULT, PCSTR},
+    Win32::{
+        Foundation::ERROR_FILE_NOT_FOUND,
+        Graphics::Direct3D::{ID3DInclude_Impl, D3D_INCLUDE_TYPE},
+    },
+};
+
+struct OpenFile {
+    // kept alive until the compiler closes the file
+    #[allow(dead_code)]
+    data: Box<[u8]>,
+    path: PathBuf,
+}
+
+/// An include handler resolving `#include` directives the way the standard one does (relative to
+/// the including file, then to the main source file, then to the /I directories, in the order
+/// given), with some extra fxc2 behavior on top. `#include "..."` and `#include <...>` are searched
+/// the same way, like fxc does. Without any /I or extra behavior, the standard handler is used
+/// instead.
+pub struct IncludeHandler {
+    source_dir: PathBuf,
+    include_dirs: Vec<PathBuf>,
+    source_relative: bool,
+    extensions: Vec<String>,
+    verbose: bool,
+    /// Files handed out to the compiler and not closed yet, keyed by their data pointer.
+    open_files: RefCell<HashMap<*const c_void, OpenFile>>,
+    /// Every file opened so far, in the order they were first included.
+    included_files: RefCell<Vec<PathBuf>>,
+}
+
+impl IncludeHandler {
+    /// `extensions` are tried, in order, when an included file can't be found by its exact name.
+    /// Without `source_relative`, only the `include_dirs` are searched.
+    /// With `verbose` set, every resolved include is reported on stderr.
+    pub fn new(
+        source_file: &Path,
+        include_dirs: Vec<PathBuf>,
+        source_relative: bool,
+        extensions: Vec<String>,
+        verbose: bool,
+    ) -> IncludeHandler {
+        IncludeHandler {
+            source_dir: source_file.parent().unwrap_or(Path::new("")).to_owned(),
+            include_dirs,
+            source_relative,
+            extensions,
+            verbose,
+            open_files: RefCell::new(HashMap::new()),
+            included_files: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The files opened for the compiler so far, each listed once, as they were resolved.
+    pub fn included_files(&self) -> Vec<PathBuf> {
+        self.included_files.borrow().clone()
+    }
+
+    fn resolve(&self, name: &str, parent_data: *const c_void) -> Option<PathBuf> {
+        let open_files = self.open_files.borrow();
+        let mut dirs = Vec::with_capacity(2 + self.include_dirs.len());
+        if self.source_relative {
+            if let Some(parent) = open_files.get(&parent_data) {
+                dirs.push(parent.path.parent().unwrap_or(Path::new("")));
+            }
+            dirs.push(&self.source_dir);
+        }
+        dirs.extend(self.include_dirs.iter().map(PathBuf::as_path));
+
+        // an exact match anywhere wins over an extension fallback
+        let exact = dirs.iter().map(|dir| dir.join(name));
+        let with_extension = self.extensions.iter().flat_map(|extension| {
+            dirs.iter()
+                .map(move |dir| dir.join(format!("{name}.{extension}")))
+        });
+        exact.chain(with_extension).find(|path| path.is_file())
+    }
+}
+
+impl IncludeHandler {
+    fn open(
+        &self,
+        name: &str,
+        pparentdata: *const c_void,
+        ppdata: *mut *mut c_void,
+        pbytes: *mut u32,
+    ) -> windows::core::Result<()> {
+        let Some(path) = self.resolve(name, pparentdata) else {
+            if self.verbose {
+                eprintln!("Could not resolve include '{name}'");
+            }
+            return Err(ERROR_FILE_NOT_FOUND.to_hresult().into());
+        };
+        if self.verbose {
+            eprintln!("Resolved include '{name}' to '{}'", path.display());
+        }
+        let data = read(&path).map_err(|err| {
+            let code = err
+                .raw_os_error()
+                .map_or(ERROR_FILE_NOT_FOUND.0, |code| code as u32);
+            HRESULT::from_win32(code)
+        })?;
+        let mut included_files = self.included_files.borrow_mut();
+        if !included_files.contains(&path) {
+            included_files.push(path.clone());
+        }
+        unsafe {
+            *ppdata = data.as_ptr() as *mut c_void;
+            *pbytes = data.len() as u32;
+        }
+        self.open_files
+            .borrow_mut()
+            .insert(data.as_ptr() as *const c_void, OpenFile { data, path });
+        Ok(())
+    }
+}
+
+/// Reads an included file as the compiler gets it.
+fn read(path: &Path) -> std::io::Result<Box<[u8]>> {
+    // like the input file, so a UTF-16 header doesn't reach the compiler as is
+    Ok(bom::transcode(fs::read(path)?).into_boxed_slice())
+}
+
+impl ID3DInclude_Impl for IncludeHandler {
+    // the compiler always hands us valid output pointers
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn Open(
+        &self,
+        includetype: D3D_INCLUDE_TYPE,
+        pfilename: &PCSTR,
+        pparentdata: *const c_void,
+        ppdata: *mut *mut c_void,
+        pbytes: *mut u32,
+    ) -> windows::core::Result<()> {
+        let name = unsafe { pfilename.to_string() }?;
+        let result = self.open(&name, pparentdata, ppdata, pbytes);
+        trace::call(
+            "ID3DInclude::Open",
+            format_args!("type {}, {name:?}, parent {pparentdata:p}", includetype.0),
+            &result,
+        );
+        result
+    }
+
+    fn Close(&self, pdata: *const c_void) -> windows::core::Result<()> {
+        self.open_files.borrow_mut().remove(&pdata);
+        trace::call("ID3DInclude::Close", format_args!("{pdata:p}"), &Ok(()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of its own for each test, under the temp directory.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("fxc2-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).expect("the temp directory is writable");
+        dir
+    }
+
+    #[test]
+    fn utf16_include() {
+        let dir = test_dir("utf16-include");
+        let mut header = vec![0xFF, 0xFE];
+        header.extend("#define X 1\n".encode_utf16().flat_map(u16::to_le_bytes));
+        fs::write(dir.join("utf16.hlsli"), header).unwrap();
+        let handler = IncludeHandler::new(&dir.join("x.hlsl"), Vec::new(), true, Vec::new(), false);
+        let path = handler.resolve("utf16.hlsli", std::ptr::null()).unwrap();
+        assert_eq!(&*read(&path).unwrap(), b"#define X 1\n");
+    }
+
+    #[test]
+    fn include_directories_in_order() {
+        let dir = test_dir("include-dirs");
+        for sub in ["src", "first", "second"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        fs::write(dir.join("first/common.hlsli"), "").unwrap();
+        fs::write(dir.join("second/common.hlsli"), "").unwrap();
+        fs::write(dir.join("second/only.hlsli"), "").unwrap();
+        fs::write(dir.join("src/common.hlsli"), "").unwrap();
+        let include_dirs = vec![dir.join("first"), dir.join("second")];
+        let source = dir.join("src/x.hlsl");
+        let resolve =
+            |handler: &IncludeHandler, name: &str| handler.resolve(name, std::ptr::null());
+
+        let handler = IncludeHandler::new(&source, include_dirs.clone(), true, Vec::new(), false);
+        // the source file's directory comes first, then each /I directory as given
+        assert_eq!(
+            resolve(&handler, "common.hlsli"),
+            Some(dir.join("src/common.hlsli"))
+        );
+        assert_eq!(
+            resolve(&handler, "only.hlsli"),
+            Some(dir.join("second/only.hlsli"))
+        );
+        assert_eq!(resolve(&handler, "missing.hlsli"), None);
+
+        let handler = IncludeHandler::new(&source, include_dirs, false, Vec::new(), false);
+        assert_eq!(
+            resolve(&handler, "common.hlsli"),
+            Some(dir.join("first/common.hlsli"))
+        );
+    }
+}
@@ -0,0 +1,23 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+pub mod args;
+pub mod bom;
+pub mod compile;
+pub mod compile_db;
+pub mod depfile;
+pub mod diagnostics;
+pub mod disasm;
+pub mod dxbc;
+pub mod hresult;
+pub mod include;
+pub mod meta;
+pub mod pattern;
+pub mod pragma;
+pub mod reflect;
+pub mod rootsig;
+pub mod summary;
+pub mod techniques;
+pub mod timings;
+pub mod trace;
@@ -0,0 +1,102 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use serde_json::{json, Value};
+
+use crate::{args::ParsedOptions, dxbc};
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Describes a compile for build system bookkeeping.
+///
+/// The schema is versioned like the reflection JSON. Hashes are MD5 as lowercase hex, `flags` are
+/// the D3DCOMPILE_* flags passed to the compiler, and `timestamp` (seconds since the Unix epoch)
+/// is only present when requested, so the file is reproducible by default:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "model": "ps_5_0",
+///   "entry_point": "main",
+///   "defines": [{ "name": "QUALITY", "value": "2" }],
+///   "flags": 2048,
+///   "input": { "file": "blur.hlsl", "md5": "0cc175b9c0f1b6a831c399e269772661" },
+///   "output": { "md5": "92eb5ffee6ae2fec3ad71c777531578f", "size": 1024 },
+///   "timestamp": 1700000000
+/// }
+/// ```
+pub fn to_json(opts: &ParsedOptions, input: &[u8], output: &[u8], timestamp: Option<u64>) -> Value {
+    let defines = opts
+        .defines
+        .iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name.to_string_lossy(),
+                "value": value.to_string_lossy(),
+            })
+        })
+        .collect::<Value>();
+    let mut meta = json!({
+        "version": 1,
+        "model": opts.model,
+        "entry_point": opts.entry_point.to_string_lossy(),
+        "defines": defines,
+        "flags": opts.flags1,
+        "input": {
+            "file": opts.input_file,
+            "md5": hex(&dxbc::md5(input)),
+        },
+        "output": {
+            "md5": hex(&dxbc::md5(output)),
+            "size": output.len(),
+        },
+    });
+    if let Some(timestamp) = timestamp {
+        meta["timestamp"] = json!(timestamp);
+    }
+    meta
+}
+
+/// Describes one file a batch wrote, for [`manifest`]. `kind` is `header` for /Fh and `object`
+/// for /Fo.
+pub fn manifest_entry(opts: &ParsedOptions, kind: &str, path: &str, contents: &[u8]) -> Value {
+    json!({
+        "path": path,
+        "kind": kind,
+        "source": opts.input_file,
+        "profile": opts.model,
+        "entry_point": opts.entry_point.to_string_lossy(),
+        "variable_name": opts.variable_name,
+        "md5": hex(&dxbc::md5(contents)),
+    })
+}
+
+/// Lists every file a batch (--techniques or --files-from) wrote, in the order they were compiled.
+///
+/// Versioned like the other JSON outputs, with `md5` the hash of the file's contents and
+/// `timestamp` only present when requested:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "artifacts": [
+///     { "path": "out/blur.h", "kind": "header", "source": "blur.hlsl", "profile": "ps_5_0",
+///       "entry_point": "main", "variable_name": "g_ps50_main",
+///       "md5": "92eb5ffee6ae2fec3ad71c777531578f" }
+///   ],
+///   "timestamp": 1700000000
+/// }
+/// ```
+pub fn manifest(artifacts: Vec<Value>, timestamp: Option<u64>) -> Value {
+    let mut manifest = json!({
+        "version": 1,
+        "artifacts": artifacts,
+    });
+    if let Some(timestamp) = timestamp {
+        manifest["timestamp"] = json!(timestamp);
+    }
+    manifest
+}
@@ -0,0 +1,297 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A small regular expression matcher for the patterns fxc2 takes on the command line.
+//!
+//! Supported syntax: literals, `.`, `[...]` and `[^...]` classes with ranges, `\d`, `\w`, `\s`,
+//! `\` to escape anything else, `^` and `$` anchors, `(...)` groups, `|` alternation and the
+//! greedy `*`, `+` and `?` quantifiers. Like most regex engines, a pattern matches if it matches
+//! anywhere in the text, anchor it to match the whole text.
+
+enum Node {
+    Char(char),
+    Any,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+pub struct Pattern {
+    source: String,
+    alternatives: Vec<Vec<Node>>,
+}
+
+impl Pattern {
+    /// Compiles the pattern. Returns a description of the problem if it's malformed.
+    pub fn new(pattern: &str) -> Result<Pattern, String> {
+        let chars = pattern.chars().collect::<Vec<_>>();
+        let mut pos = 0;
+        let alternatives = parse_alternatives(&chars, &mut pos)?;
+        if pos < chars.len() {
+            return Err(format!("unmatched ')' at offset {pos}"));
+        }
+        Ok(Pattern {
+            source: pattern.to_owned(),
+            alternatives,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns true if the pattern matches anywhere in the text.
+    pub fn is_match(&self, text: &str) -> bool {
+        let text = text.chars().collect::<Vec<_>>();
+        (0..=text.len()).any(|start| {
+            self.alternatives
+                .iter()
+                .any(|nodes| match_nodes(nodes, &text, start, &mut |_| true))
+        })
+    }
+}
+
+fn parse_alternatives(chars: &[char], pos: &mut usize) -> Result<Vec<Vec<Node>>, String> {
+    let mut alternatives = vec![parse_sequence(chars, pos)?];
+    while chars.get(*pos) == Some(&'|') {
+        *pos += 1;
+        alternatives.push(parse_sequence(chars, pos)?);
+    }
+    Ok(alternatives)
+}
+
+fn parse_sequence(chars: &[char], pos: &mut usize) -> Result<Vec<Node>, String> {
+    let mut nodes = Vec::new();
+    while let Some(&c) = chars.get(*pos) {
+        if c == '|' || c == ')' {
+            break;
+        }
+        *pos += 1;
+        let node = match c {
+            '(' => {
+                let alternatives = parse_alternatives(chars, pos)?;
+                if chars.get(*pos) != Some(&')') {
+                    return Err("unterminated group".to_owned());
+                }
+                *pos += 1;
+                Node::Group(alternatives)
+            }
+            '[' => parse_class(chars, pos)?,
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '\\' => parse_escape(chars, pos)?,
+            '*' | '+' | '?' => return Err(format!("nothing to repeat at offset {}", *pos - 1)),
+            c => Node::Char(c),
+        };
+        let (min, max) = match chars.get(*pos) {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            _ => {
+                nodes.push(node);
+                continue;
+            }
+        };
+        *pos += 1;
+        nodes.push(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+        });
+    }
+    Ok(nodes)
+}
+
+fn parse_escape(chars: &[char], pos: &mut usize) -> Result<Node, String> {
+    let c = *chars.get(*pos).ok_or("trailing '\\'")?;
+    *pos += 1;
+    let ranges = match c {
+        'd' => vec![('0', '9')],
+        'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+        's' => vec![(' ', ' '), ('\t', '\r')],
+        c => return Ok(Node::Char(c)),
+    };
+    Ok(Node::Class {
+        ranges,
+        negated: false,
+    })
+}
+
+fn parse_class(chars: &[char], pos: &mut usize) -> Result<Node, String> {
+    let negated = chars.get(*pos) == Some(&'^');
+    if negated {
+        *pos += 1;
+    }
+    let mut ranges = Vec::new();
+    loop {
+        let c = match chars.get(*pos) {
+            None => return Err("unterminated '['".to_owned()),
+            Some(']') if !ranges.is_empty() => break,
+            Some(&c) => c,
+        };
+        *pos += 1;
+        let start = if c == '\\' {
+            match parse_escape(chars, pos)? {
+                Node::Char(c) => c,
+                Node::Class { ranges: class, .. } => {
+                    ranges.extend(class);
+                    continue;
+                }
+                _ => unreachable!(),
+            }
+        } else {
+            c
+        };
+        if chars.get(*pos) == Some(&'-') && chars.get(*pos + 1).is_some_and(|c| *c != ']') {
+            let end = chars[*pos + 1];
+            *pos += 2;
+            if end < start {
+                return Err(format!("invalid range '{start}-{end}'"));
+            }
+            ranges.push((start, end));
+        } else {
+            ranges.push((start, start));
+        }
+    }
+    *pos += 1;
+    Ok(Node::Class { ranges, negated })
+}
+
+/// Matches the nodes at `pos`, calling `k` with every position a match could end at until it
+/// returns true.
+fn match_nodes(
+    nodes: &[Node],
+    text: &[char],
+    pos: usize,
+    k: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    let Some((node, rest)) = nodes.split_first() else {
+        return k(pos);
+    };
+    match node {
+        Node::Start => pos == 0 && match_nodes(rest, text, pos, k),
+        Node::End => pos == text.len() && match_nodes(rest, text, pos, k),
+        Node::Repeat { node, min, max } => match_repeat(node, *min, *max, 0, rest, text, pos, k),
+        node => match_one(node, text, pos, &mut |next| {
+            match_nodes(rest, text, next, k)
+        }),
+    }
+}
+
+fn match_one(node: &Node, text: &[char], pos: usize, k: &mut dyn FnMut(usize) -> bool) -> bool {
+    let matches = |c: char| match node {
+        Node::Char(expected) => c == *expected,
+        Node::Any => true,
+        Node::Class { ranges, negated } => {
+            ranges
+                .iter()
+                .any(|(start, end)| (*start..=*end).contains(&c))
+                != *negated
+        }
+        _ => false,
+    };
+    match node {
+        Node::Group(alternatives) => alternatives
+            .iter()
+            .any(|nodes| match_nodes(nodes, text, pos, k)),
+        Node::Start | Node::End | Node::Repeat { .. } => {
+            match_nodes(std::slice::from_ref(node), text, pos, k)
+        }
+        _ => text.get(pos).is_some_and(|c| matches(*c)) && k(pos + 1),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn match_repeat(
+    node: &Node,
+    min: usize,
+    max: Option<usize>,
+    count: usize,
+    rest: &[Node],
+    text: &[char],
+    pos: usize,
+    k: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    // greedy: try one more repetition first, an empty one would loop forever
+    if max.is_none_or(|max| count < max)
+        && match_one(node, text, pos, &mut |next| {
+            next != pos && match_repeat(node, min, max, count + 1, rest, text, next, k)
+        })
+    {
+        return true;
+    }
+    count >= min && match_nodes(rest, text, pos, k)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Pattern::new(pattern).unwrap().is_match(text)
+    }
+
+    fn error(pattern: &str) -> String {
+        Pattern::new(pattern).err().unwrap()
+    }
+
+    #[test]
+    fn anchors() {
+        assert!(matches("Main", "VSMain"));
+        assert!(!matches("^Main", "VSMain"));
+        assert!(matches("^[VP]SMain$", "PSMain"));
+        assert!(!matches("^[VP]SMain$", "PSMain2"));
+        assert!(matches("", "anything"));
+        assert!(matches("^$", ""));
+    }
+
+    #[test]
+    fn classes_and_escapes() {
+        assert!(matches(r"^\w+_\d$", "main_5"));
+        assert!(!matches(r"^\w+_\d$", "main_x"));
+        assert!(matches(r"^[^0-9]+$", "main"));
+        assert!(!matches(r"^[^0-9]+$", "main2"));
+        assert!(matches(r"a\.b", "a.b"));
+        assert!(!matches(r"a\.b", "axb"));
+        assert!(matches(r"^\s$", "\t"));
+        // `]` first and `-` last are literals
+        assert!(matches("^[]a-]+$", "]-a"));
+        assert!(matches(r"^[\d_]+$", "1_2"));
+    }
+
+    #[test]
+    fn groups_and_repetition() {
+        assert!(matches("^(VS|PS)Main$", "VSMain"));
+        assert!(!matches("^(VS|PS)Main$", "CSMain"));
+        assert!(matches("^ps_(main|(pass)+)$", "ps_passpass"));
+        assert!(matches("^a?b+c*$", "bbb"));
+        assert!(!matches("^a?b+c*$", "aac"));
+        // needs backtracking into the greedy repetition
+        assert!(matches("^.*Main$", "MainMain"));
+        assert!(matches("^(a|ab)(c|bcd)(d*)$", "abcd"));
+        // repeating something that can match empty still terminates
+        assert!(matches("^(a*)*$", "aaa"));
+        assert!(!matches("^(a*)*$", "aab"));
+    }
+
+    #[test]
+    fn malformed_patterns() {
+        assert_eq!(error("(main"), "unterminated group");
+        assert_eq!(error("main)"), "unmatched ')' at offset 4");
+        assert_eq!(error("*main"), "nothing to repeat at offset 0");
+        assert_eq!(error("[a-z"), "unterminated '['");
+        assert_eq!(error("[z-a]"), "invalid range 'z-a'");
+        assert_eq!(error("main\\"), "trailing '\\'");
+    }
+}
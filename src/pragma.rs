@@ -0,0 +1,148 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The `#pragma fxc_flags(...)` annotation: compile flags kept in the shader source.
+//!
+//! A line of the form
+//!
+//! ```hlsl
+//! #pragma fxc_flags(WX, O3, Zpr)
+//! ```
+//!
+//! lists flags by their fxc name, without the leading `/`, separated by commas. Only flags that
+//! take no argument can be listed (`WX`, `Od`, `O0`-`O3`, `Zi`, `Zpc`, `Gfa`, ...). The flags are
+//! applied as if they came first on the command line, so the command line overrides them. Only the
+//! first [`SCAN_LIMIT`] bytes of the source are scanned, a pragma after that is ignored (and left
+//! to the compiler, which ignores unknown pragmas). The scanned pragmas are blanked out of the
+//! source handed to the compiler. An unknown flag name is an error.
+
+/// How many bytes at the start of the source are scanned for the pragma.
+pub const SCAN_LIMIT: usize = 4096;
+
+/// Returns the flag names of every `#pragma fxc_flags` in the first [`SCAN_LIMIT`] bytes of
+/// `source`, in order. Returns a description of the first malformed pragma.
+pub fn fxc_flags(source: &str) -> Result<Vec<&str>, String> {
+    // ends at a line break, so on a char boundary
+    let prefix = &source[..scanned_len(source.as_bytes())];
+
+    let mut flags = Vec::new();
+    for (i, line) in prefix.lines().enumerate() {
+        let Some(rest) = pragma_arguments(line) else {
+            continue;
+        };
+        let list = rest
+            .trim()
+            .strip_prefix('(')
+            .and_then(|rest| rest.strip_suffix(')'))
+            .ok_or_else(|| {
+                format!(
+                    "line {}: expected `#pragma fxc_flags(<flag>, ...)`, got `{}`",
+                    i + 1,
+                    line.trim()
+                )
+            })?;
+        for flag in list.split(',').map(str::trim) {
+            if flag.is_empty() {
+                return Err(format!(
+                    "line {}: empty flag name in `{}`",
+                    i + 1,
+                    line.trim()
+                ));
+            }
+            flags.push(flag);
+        }
+    }
+    Ok(flags)
+}
+
+/// Replaces every `#pragma fxc_flags` line found by [`fxc_flags`] with spaces, so the compiler
+/// doesn't warn about an unknown pragma (an error under `/WX`) and line numbers stay the same.
+pub fn blank(source: &mut [u8]) {
+    let end = scanned_len(source);
+    for line in source[..end].split_mut(|&b| b == b'\n') {
+        let text = line.strip_suffix(b"\r").unwrap_or(line);
+        let is_pragma =
+            std::str::from_utf8(text).is_ok_and(|text| pragma_arguments(text).is_some());
+        if is_pragma {
+            let len = text.len();
+            line[..len].fill(b' ');
+        }
+    }
+}
+
+/// How many bytes of `source` are scanned: up to [`SCAN_LIMIT`], without a line cut off by it.
+fn scanned_len(source: &[u8]) -> usize {
+    if source.len() <= SCAN_LIMIT {
+        return source.len();
+    }
+    source[..SCAN_LIMIT]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .unwrap_or(0)
+}
+
+/// The rest of the line after `#pragma fxc_flags`, or `None` for any other line.
+fn pragma_arguments(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('#')?;
+    let rest = rest.trim_start().strip_prefix("pragma")?;
+    let rest = rest.trim_start().strip_prefix("fxc_flags")?;
+    rest.starts_with(|c: char| c == '(' || c.is_whitespace())
+        .then_some(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_in_order() {
+        let source = "// flags\n#pragma fxc_flags(WX, O3)\n  #  pragma  fxc_flags ( Zpr )\n#pragma once\n#pragma fxc_flagsX(Od)\nfloat4 main() : SV_Target { return 0; }\n";
+        assert_eq!(fxc_flags(source), Ok(vec!["WX", "O3", "Zpr"]));
+        assert_eq!(fxc_flags("float4 main() : SV_Target;"), Ok(vec![]));
+    }
+
+    #[test]
+    fn malformed_pragma() {
+        assert_eq!(
+            fxc_flags("\n#pragma fxc_flags WX\n"),
+            Err(
+                "line 2: expected `#pragma fxc_flags(<flag>, ...)`, got `#pragma fxc_flags WX`"
+                    .to_owned()
+            )
+        );
+        assert_eq!(
+            fxc_flags("#pragma fxc_flags(WX,,O3)"),
+            Err("line 1: empty flag name in `#pragma fxc_flags(WX,,O3)`".to_owned())
+        );
+    }
+
+    #[test]
+    fn scan_limit() {
+        let padding = format!("//{}\n", "x".repeat(SCAN_LIMIT - 30));
+        let source = format!("{padding}#pragma fxc_flags(WX)\n#pragma fxc_flags(O3)\n");
+        // the first pragma ends before the limit, the second one crosses it
+        assert!(padding.len() + 22 <= SCAN_LIMIT);
+        assert!(padding.len() + 44 > SCAN_LIMIT);
+        assert_eq!(fxc_flags(&source), Ok(vec!["WX"]));
+
+        let mut blanked = source.clone().into_bytes();
+        blank(&mut blanked);
+        let expected = format!("{padding}{}\n#pragma fxc_flags(O3)\n", " ".repeat(21));
+        assert_eq!(String::from_utf8(blanked).unwrap(), expected);
+    }
+
+    #[test]
+    fn blank_keeps_lines() {
+        let mut source = b"#pragma fxc_flags(WX)\r\nfloat x; // #pragma fxc_flags(O3)\r\n".to_vec();
+        blank(&mut source);
+        assert_eq!(
+            source,
+            format!(
+                "{}\r\nfloat x; // #pragma fxc_flags(O3)\r\n",
+                " ".repeat(21)
+            )
+            .as_bytes()
+        );
+    }
+}
@@ -0,0 +1,428 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ffi::c_void;
+
+use crate::trace;
+
+use serde_json::{json, Value};
+use windows::{
+    core::{ComInterface, Interface},
+    Win32::Graphics::{
+        Direct3D::Fxc::D3DReflect,
+        Direct3D12::{
+            ID3D12ShaderReflection, D3D12_SHADER_BUFFER_DESC, D3D12_SHADER_DESC,
+            D3D12_SHADER_INPUT_BIND_DESC, D3D12_SHADER_VARIABLE_DESC,
+            D3D12_SIGNATURE_PARAMETER_DESC,
+        },
+    },
+};
+
+/// One element of an input or output signature.
+pub struct SignatureParameter {
+    pub semantic_name: String,
+    pub semantic_index: u32,
+    pub register: u32,
+    /// D3D_NAME
+    pub system_value: i32,
+    /// D3D_REGISTER_COMPONENT_TYPE
+    pub component_type: i32,
+    pub mask: u8,
+    pub read_write_mask: u8,
+    pub stream: u32,
+}
+
+impl SignatureParameter {
+    fn from_desc(desc: &D3D12_SIGNATURE_PARAMETER_DESC) -> SignatureParameter {
+        SignatureParameter {
+            semantic_name: unsafe { desc.SemanticName.to_string() }.unwrap_or_default(),
+            semantic_index: desc.SemanticIndex,
+            register: desc.Register,
+            system_value: desc.SystemValueType.0,
+            component_type: desc.ComponentType.0,
+            mask: desc.Mask,
+            read_write_mask: desc.ReadWriteMask,
+            stream: desc.Stream,
+        }
+    }
+
+    /// Number of components in the register, counting up to the highest one used.
+    pub fn component_count(&self) -> u32 {
+        8 - self.mask.leading_zeros()
+    }
+
+    /// The DXGI_FORMAT an input layout element would use to feed this parameter, as (value, name).
+    pub fn dxgi_format(&self) -> (u32, &'static str) {
+        // D3D_REGISTER_COMPONENT_UINT32, D3D_REGISTER_COMPONENT_SINT32, D3D_REGISTER_COMPONENT_FLOAT32
+        match (self.component_type, self.component_count()) {
+            (1, 1) => (42, "DXGI_FORMAT_R32_UINT"),
+            (2, 1) => (43, "DXGI_FORMAT_R32_SINT"),
+            (3, 1) => (41, "DXGI_FORMAT_R32_FLOAT"),
+            (1, 2) => (17, "DXGI_FORMAT_R32G32_UINT"),
+            (2, 2) => (18, "DXGI_FORMAT_R32G32_SINT"),
+            (3, 2) => (16, "DXGI_FORMAT_R32G32_FLOAT"),
+            (1, 3) => (7, "DXGI_FORMAT_R32G32B32_UINT"),
+            (2, 3) => (8, "DXGI_FORMAT_R32G32B32_SINT"),
+            (3, 3) => (6, "DXGI_FORMAT_R32G32B32_FLOAT"),
+            (1, 4) => (3, "DXGI_FORMAT_R32G32B32A32_UINT"),
+            (2, 4) => (4, "DXGI_FORMAT_R32G32B32A32_SINT"),
+            (3, 4) => (2, "DXGI_FORMAT_R32G32B32A32_FLOAT"),
+            _ => (0, "DXGI_FORMAT_UNKNOWN"),
+        }
+    }
+}
+
+/// A variable inside a constant buffer.
+pub struct Variable {
+    pub name: String,
+    pub offset: u32,
+    pub size: u32,
+}
+
+pub struct ConstantBuffer {
+    pub name: String,
+    pub size: u32,
+    pub variables: Vec<Variable>,
+}
+
+/// A resource (constant buffer, texture, sampler, UAV, ...) bound to the shader.
+pub struct ResourceBinding {
+    pub name: String,
+    /// D3D_SHADER_INPUT_TYPE
+    pub input_type: i32,
+    pub bind_point: u32,
+    pub bind_count: u32,
+    pub space: u32,
+}
+
+impl ResourceBinding {
+    /// A short, stable name for the D3D_SHADER_INPUT_TYPE of the resource.
+    pub fn input_type_name(&self) -> &'static str {
+        match self.input_type {
+            0 => "cbuffer",
+            1 => "tbuffer",
+            2 => "texture",
+            3 => "sampler",
+            4 => "uav_rwtyped",
+            5 => "structured",
+            6 => "uav_rwstructured",
+            7 => "byteaddress",
+            8 => "uav_rwbyteaddress",
+            9 => "uav_append_structured",
+            10 => "uav_consume_structured",
+            11 => "uav_rwstructured_with_counter",
+            12 => "rtaccelerationstructure",
+            13 => "uav_feedbacktexture",
+            _ => "unknown",
+        }
+    }
+
+    /// The HLSL register class the resource is bound in: `b`, `t`, `s` or `u`.
+    pub fn register_class(&self) -> char {
+        match self.input_type {
+            0 => 'b',
+            3 => 's',
+            4 | 6 | 8..=11 | 13 => 'u',
+            _ => 't',
+        }
+    }
+}
+
+/// Safe wrapper around the shader reflection interface of a compiled blob.
+pub struct ShaderReflection {
+    reflection: ID3D12ShaderReflection,
+    desc: D3D12_SHADER_DESC,
+}
+
+impl ShaderReflection {
+    pub fn new(bytecode: &[u8]) -> windows::core::Result<ShaderReflection> {
+        let mut raw: *mut c_void = std::ptr::null_mut();
+        let result = unsafe {
+            D3DReflect(
+                bytecode.as_ptr() as *const c_void,
+                bytecode.len(),
+                &ID3D12ShaderReflection::IID,
+                &mut raw,
+            )
+        };
+        trace::call(
+            "D3DReflect",
+            format_args!("{} bytes, ID3D12ShaderReflection", bytecode.len()),
+            &result,
+        );
+        result?;
+        let reflection = unsafe { ID3D12ShaderReflection::from_raw(raw) };
+        let mut desc = D3D12_SHADER_DESC::default();
+        unsafe { reflection.GetDesc(&mut desc) }?;
+        Ok(ShaderReflection { reflection, desc })
+    }
+
+    /// The profile the shader was compiled for, e.g. `ps_5_0`.
+    pub fn profile(&self) -> String {
+        let stage = match self.desc.Version >> 16 {
+            0 => "ps",
+            1 => "vs",
+            2 => "gs",
+            3 => "hs",
+            4 => "ds",
+            5 => "cs",
+            _ => "unknown",
+        };
+        let major = (self.desc.Version >> 4) & 0xf;
+        let minor = self.desc.Version & 0xf;
+        format!("{stage}_{major}_{minor}")
+    }
+
+    /// The `[numthreads(x, y, z)]` of a compute shader, zeros for the other stages.
+    pub fn thread_group_size(&self) -> [u32; 3] {
+        let mut size = [0; 3];
+        let [x, y, z] = &mut size;
+        unsafe {
+            self.reflection
+                .GetThreadGroupSize(Some(x), Some(y), Some(z))
+        };
+        size
+    }
+
+    pub fn instruction_count(&self) -> u32 {
+        self.desc.InstructionCount
+    }
+
+    pub fn constant_buffers(&self) -> windows::core::Result<Vec<ConstantBuffer>> {
+        (0..self.desc.ConstantBuffers)
+            .filter_map(|i| unsafe { self.reflection.GetConstantBufferByIndex(i) })
+            .map(|buffer| {
+                let mut desc = D3D12_SHADER_BUFFER_DESC::default();
+                unsafe { buffer.GetDesc(&mut desc) }?;
+                let variables = (0..desc.Variables)
+                    .filter_map(|i| unsafe { buffer.GetVariableByIndex(i) })
+                    .map(|variable| {
+                        let mut desc = D3D12_SHADER_VARIABLE_DESC::default();
+                        unsafe { variable.GetDesc(&mut desc) }?;
+                        Ok(Variable {
+                            name: unsafe { desc.Name.to_string() }.unwrap_or_default(),
+                            offset: desc.StartOffset,
+                            size: desc.Size,
+                        })
+                    })
+                    .collect::<windows::core::Result<_>>()?;
+                Ok(ConstantBuffer {
+                    name: unsafe { desc.Name.to_string() }.unwrap_or_default(),
+                    size: desc.Size,
+                    variables,
+                })
+            })
+            .collect()
+    }
+
+    pub fn resource_bindings(&self) -> windows::core::Result<Vec<ResourceBinding>> {
+        (0..self.desc.BoundResources)
+            .map(|i| {
+                let mut desc = D3D12_SHADER_INPUT_BIND_DESC::default();
+                unsafe { self.reflection.GetResourceBindingDesc(i, &mut desc) }?;
+                Ok(ResourceBinding {
+                    name: unsafe { desc.Name.to_string() }.unwrap_or_default(),
+                    input_type: desc.Type.0,
+                    bind_point: desc.BindPoint,
+                    bind_count: desc.BindCount,
+                    space: desc.Space,
+                })
+            })
+            .collect()
+    }
+
+    pub fn input_signature(&self) -> windows::core::Result<Vec<SignatureParameter>> {
+        (0..self.desc.InputParameters)
+            .map(|i| {
+                let mut desc = D3D12_SIGNATURE_PARAMETER_DESC::default();
+                unsafe { self.reflection.GetInputParameterDesc(i, &mut desc) }?;
+                Ok(SignatureParameter::from_desc(&desc))
+            })
+            .collect()
+    }
+
+    pub fn output_signature(&self) -> windows::core::Result<Vec<SignatureParameter>> {
+        (0..self.desc.OutputParameters)
+            .map(|i| {
+                let mut desc = D3D12_SIGNATURE_PARAMETER_DESC::default();
+                unsafe { self.reflection.GetOutputParameterDesc(i, &mut desc) }?;
+                Ok(SignatureParameter::from_desc(&desc))
+            })
+            .collect()
+    }
+}
+
+/// Serializes the reflection data to JSON.
+///
+/// The schema is versioned by the top-level `version` field, which is bumped whenever an existing
+/// field changes meaning or is removed (new fields may be added without a bump):
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "profile": "ps_5_0",
+///   "instruction_count": 12,
+///   "constant_buffers": [
+///     { "name": "Globals", "size": 64, "variables": [{ "name": "color", "offset": 0, "size": 16 }] }
+///   ],
+///   "resources": [
+///     { "name": "tex", "type": "texture", "bind_point": 0, "bind_count": 1, "space": 0 }
+///   ],
+///   "input_signature": [
+///     { "semantic_name": "TEXCOORD", "semantic_index": 0, "register": 0, "system_value": 0,
+///       "component_type": 3, "mask": 3 }
+///   ],
+///   "output_signature": []
+/// }
+/// ```
+///
+/// `type` is one of the names returned by [`ResourceBinding::input_type_name`], `system_value` a
+/// `D3D_NAME` and `component_type` a `D3D_REGISTER_COMPONENT_TYPE`.
+pub fn to_json(reflection: &ShaderReflection) -> windows::core::Result<Value> {
+    fn signature(parameters: Vec<SignatureParameter>) -> Value {
+        parameters
+            .iter()
+            .map(|parameter| {
+                json!({
+                    "semantic_name": parameter.semantic_name,
+                    "semantic_index": parameter.semantic_index,
+                    "register": parameter.register,
+                    "system_value": parameter.system_value,
+                    "component_type": parameter.component_type,
+                    "mask": parameter.mask,
+                })
+            })
+            .collect()
+    }
+
+    let constant_buffers = reflection
+        .constant_buffers()?
+        .iter()
+        .map(|buffer| {
+            let variables = buffer
+                .variables
+                .iter()
+                .map(|variable| {
+                    json!({
+                        "name": variable.name,
+                        "offset": variable.offset,
+                        "size": variable.size,
+                    })
+                })
+                .collect::<Value>();
+            json!({
+                "name": buffer.name,
+                "size": buffer.size,
+                "variables": variables,
+            })
+        })
+        .collect::<Value>();
+    let resources = reflection
+        .resource_bindings()?
+        .iter()
+        .map(|resource| {
+            json!({
+                "name": resource.name,
+                "type": resource.input_type_name(),
+                "bind_point": resource.bind_point,
+                "bind_count": resource.bind_count,
+                "space": resource.space,
+            })
+        })
+        .collect::<Value>();
+
+    Ok(json!({
+        "version": 1,
+        "profile": reflection.profile(),
+        "instruction_count": reflection.instruction_count(),
+        "constant_buffers": constant_buffers,
+        "resources": resources,
+        "input_signature": signature(reflection.input_signature()?),
+        "output_signature": signature(reflection.output_signature()?),
+    }))
+}
+
+/// Writes the register of every resource the shader binds as HLSL macros, so that other shaders
+/// of the pipeline can declare the same resources in the same registers.
+///
+/// Each resource gets two macros, named after it with any character that can't be in an
+/// identifier replaced by `_` (the global constant buffer `$Globals` becomes `_Globals`):
+///
+/// ```hlsl
+/// #define tex_REGISTER register(t0, space1)
+/// #define tex_SLOT 0
+/// ```
+///
+/// `_REGISTER` is for declarations (`Texture2D tex : tex_REGISTER;`), the space is only given when
+/// it isn't 0. `_SLOT` is the bind point alone, for `#if` checks.
+pub fn bindings_hlsl(reflection: &ShaderReflection) -> windows::core::Result<String> {
+    let mut hlsl = format!(
+        "// Registers of the {} shader's resources, generated by fxc2\n",
+        reflection.profile()
+    );
+    for resource in reflection.resource_bindings()? {
+        let name = resource
+            .name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        let class = resource.register_class();
+        let register = match resource.space {
+            0 => format!("register({class}{})", resource.bind_point),
+            space => format!("register({class}{}, space{space})", resource.bind_point),
+        };
+        hlsl.push_str(&format!(
+            "\n// {} {}\n",
+            resource.input_type_name(),
+            resource.name
+        ));
+        hlsl.push_str(&format!("#define {name}_REGISTER {register}\n"));
+        hlsl.push_str(&format!("#define {name}_SLOT {}\n", resource.bind_point));
+    }
+    Ok(hlsl)
+}
+
+/// Maps the shader's registers to Vulkan descriptor sets and bindings, as JSON.
+///
+/// The register space becomes the set. Since Vulkan has a single binding namespace per set, each
+/// register class is shifted into its own range, like DXC's `-fvk-{b,t,s,u}-shift` options:
+/// `b` registers start at binding 0, `t` at 1000, `s` at 2000 and `u` at 3000.
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "bindings": [
+///     { "name": "tex", "type": "texture", "register": "t0", "space": 0, "count": 1,
+///       "set": 0, "binding": 1000 }
+///   ]
+/// }
+/// ```
+pub fn descriptor_map(reflection: &ShaderReflection) -> windows::core::Result<Value> {
+    let bindings = reflection
+        .resource_bindings()?
+        .iter()
+        .map(|resource| {
+            let class = resource.register_class();
+            let shift = match class {
+                'b' => 0,
+                't' => 1000,
+                's' => 2000,
+                _ => 3000,
+            };
+            json!({
+                "name": resource.name,
+                "type": resource.input_type_name(),
+                "register": format!("{class}{}", resource.bind_point),
+                "space": resource.space,
+                "count": resource.bind_count,
+                "set": resource.space,
+                "binding": shift + resource.bind_point,
+            })
+        })
+        .collect::<Value>();
+    Ok(json!({
+        "version": 1,
+        "bindings": bindings,
+    }))
+}
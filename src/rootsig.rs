@@ -0,0 +1,273 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Reading serialized D3D12 root signatures (the `RTS0` part), to check a shader against one.
+//!
+//! The part starts with a 24 byte header: version (1 for 1.0, 2 for 1.1), the number and offset of
+//! the root parameters, the number and offset of the static samplers, and the flags. A root
+//! parameter is its type, its shader visibility and the offset of its payload. Offsets are from the
+//! start of the part, and all values are little-endian u32s.
+
+use crate::{dxbc, reflect::ResourceBinding};
+
+/// Descriptor ranges of version 1.1 have flags before the offset in the table.
+const RANGE_SIZE_1_0: usize = 20;
+const RANGE_SIZE_1_1: usize = 24;
+const STATIC_SAMPLER_SIZE: usize = 52;
+/// The descriptor count of an unbounded range
+const UNBOUNDED: u32 = u32::MAX;
+
+/// A register range the root signature makes available.
+pub struct Registers {
+    /// D3D12_SHADER_VISIBILITY: 0 for all stages, then vs, hs, ds, gs, ps
+    pub visibility: u32,
+    /// The HLSL register class: `b`, `t`, `s` or `u`
+    pub register_class: char,
+    pub space: u32,
+    pub first: u32,
+    /// None for an unbounded range
+    pub count: Option<u32>,
+}
+
+impl Registers {
+    fn visible_to(&self, stage: &str) -> bool {
+        let stages = ["", "vs", "hs", "ds", "gs", "ps"];
+        self.visibility == 0 || stages.get(self.visibility as usize) == Some(&stage)
+    }
+
+    fn contains(&self, binding: &ResourceBinding) -> bool {
+        if binding.register_class() != self.register_class || binding.space != self.space {
+            return false;
+        }
+        let Some(offset) = binding.bind_point.checked_sub(self.first) else {
+            return false;
+        };
+        match (self.count, binding.bind_count) {
+            (None, _) => true,
+            // an unbounded array needs an unbounded range
+            (Some(_), 0) => false,
+            (Some(count), bind_count) => offset as u64 + bind_count as u64 <= count as u64,
+        }
+    }
+}
+
+/// Returns the registers of a root signature: either a container holding an `RTS0` part (a
+/// compiled `rootsig_1_*` or a shader with one embedded) or the part on its own.
+pub fn registers(data: &[u8]) -> Result<Vec<Registers>, String> {
+    let part = if dxbc::is_container(data) {
+        dxbc::parts(data)
+            .ok_or("invalid container")?
+            .into_iter()
+            .find(|part| part.fourcc == *dxbc::ROOT_SIGNATURE)
+            .ok_or("the container has no root signature")?
+            .data
+    } else {
+        data
+    };
+    let truncated = || "truncated root signature".to_owned();
+    let read = |offset: usize| dxbc::read_u32(part, offset).ok_or_else(truncated);
+
+    let range_size = match read(0)? {
+        1 => RANGE_SIZE_1_0,
+        2 => RANGE_SIZE_1_1,
+        version => return Err(format!("unknown root signature version {version}")),
+    };
+    let mut registers = Vec::new();
+    let (parameter_count, parameters) = (read(4)? as usize, read(8)? as usize);
+    for i in 0..parameter_count {
+        let parameter = parameters + i * 12;
+        let visibility = read(parameter + 4)?;
+        let payload = read(parameter + 8)? as usize;
+        let register_class = match read(parameter)? {
+            0 => {
+                let (range_count, ranges) = (read(payload)? as usize, read(payload + 4)? as usize);
+                for j in 0..range_count {
+                    let range = ranges + j * range_size;
+                    let register_class = match read(range)? {
+                        0 => 't',
+                        1 => 'u',
+                        2 => 'b',
+                        3 => 's',
+                        range_type => return Err(format!("unknown range type {range_type}")),
+                    };
+                    let count = read(range + 4)?;
+                    registers.push(Registers {
+                        visibility,
+                        register_class,
+                        space: read(range + 12)?,
+                        first: read(range + 8)?,
+                        count: Some(count).filter(|&count| count != UNBOUNDED),
+                    });
+                }
+                continue;
+            }
+            // root constants and root descriptors start with the register and space
+            1 | 2 => 'b',
+            3 => 't',
+            4 => 'u',
+            parameter_type => return Err(format!("unknown parameter type {parameter_type}")),
+        };
+        registers.push(Registers {
+            visibility,
+            register_class,
+            space: read(payload + 4)?,
+            first: read(payload)?,
+            count: Some(1),
+        });
+    }
+    let (sampler_count, samplers) = (read(12)? as usize, read(16)? as usize);
+    for i in 0..sampler_count {
+        let sampler = samplers + i * STATIC_SAMPLER_SIZE;
+        registers.push(Registers {
+            visibility: read(sampler + 48)?,
+            register_class: 's',
+            space: read(sampler + 44)?,
+            first: read(sampler + 40)?,
+            count: Some(1),
+        });
+    }
+    Ok(registers)
+}
+
+/// The resources of a shader of the given stage (the profile's prefix, e.g. `ps`) that no register
+/// range of the root signature visible to that stage covers.
+pub fn unbound<'a>(
+    registers: &[Registers],
+    stage: &str,
+    bindings: &'a [ResourceBinding],
+) -> Vec<&'a ResourceBinding> {
+    bindings
+        .iter()
+        .filter(|binding| {
+            !registers
+                .iter()
+                .any(|range| range.visible_to(stage) && range.contains(binding))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A version 1.1 root signature:
+    /// - a descriptor table visible to ps, with t0-t3 and the unbounded u2+ in space1
+    /// - a CBV at b0 visible to all stages
+    /// - root constants at b1 visible to vs
+    /// - a static sampler at s3 visible to all stages
+    fn root_signature() -> Vec<u8> {
+        // header: version, 3 parameters at 24, 1 static sampler at 140, flags
+        let mut words = vec![2, 3, 24, 1, 140, 0];
+        // parameters: type, visibility, payload offset
+        words.extend([0, 5, 60, 2, 0, 116, 1, 1, 128]);
+        // the table at 60: 2 ranges at 68 of type, count, first, space, flags, table offset
+        words.extend([2, 68]);
+        words.extend([0, 4, 0, 0, 0, 0]);
+        words.extend([1, UNBOUNDED, 2, 1, 0, 4]);
+        // the CBV at 116: register, space, flags
+        words.extend([0, 0, 0]);
+        // the root constants at 128: register, space, number of values
+        words.extend([1, 0, 4]);
+        // the static sampler at 140: filter, addressing and such, then register, space, visibility
+        words.extend([0; 10]);
+        words.extend([3, 0, 0]);
+        words.into_iter().flat_map(u32::to_le_bytes).collect()
+    }
+
+    fn summary(registers: &[Registers]) -> Vec<(u32, char, u32, u32, Option<u32>)> {
+        registers
+            .iter()
+            .map(|range| {
+                (
+                    range.visibility,
+                    range.register_class,
+                    range.space,
+                    range.first,
+                    range.count,
+                )
+            })
+            .collect()
+    }
+
+    fn binding(
+        name: &str,
+        input_type: i32,
+        bind_point: u32,
+        bind_count: u32,
+        space: u32,
+    ) -> ResourceBinding {
+        ResourceBinding {
+            name: name.to_owned(),
+            input_type,
+            bind_point,
+            bind_count,
+            space,
+        }
+    }
+
+    #[test]
+    fn read_registers() {
+        let expected = [
+            (5, 't', 0, 0, Some(4)),
+            (5, 'u', 1, 2, None),
+            (0, 'b', 0, 0, Some(1)),
+            (1, 'b', 0, 1, Some(1)),
+            (0, 's', 0, 3, Some(1)),
+        ];
+        let part = root_signature();
+        assert_eq!(part.len(), 192);
+        assert_eq!(summary(&registers(&part).unwrap()), expected);
+        // the same inside a container
+        let container = dxbc::build(&[dxbc::Part {
+            fourcc: *dxbc::ROOT_SIGNATURE,
+            data: &part,
+        }]);
+        assert_eq!(summary(&registers(&container).unwrap()), expected);
+    }
+
+    #[test]
+    fn invalid_root_signatures() {
+        let error = |data: &[u8]| registers(data).err().unwrap();
+        let mut part = root_signature();
+        part[0] = 3;
+        assert_eq!(error(&part), "unknown root signature version 3");
+        assert_eq!(error(&root_signature()[..150]), "truncated root signature");
+        let container = dxbc::build(&[dxbc::Part {
+            fourcc: *b"SHEX",
+            data: &[0; 4],
+        }]);
+        assert_eq!(error(&container), "the container has no root signature");
+    }
+
+    #[test]
+    fn unbound_resources() {
+        let registers = registers(&root_signature()).unwrap();
+        // input types: 0 cbuffer, 2 texture, 3 sampler, 4 RWTyped
+        let bindings = [
+            binding("t3", 2, 3, 1, 0),
+            // past the end of t0-t3
+            binding("t3-t4", 2, 3, 2, 0),
+            // unbounded, while only t0-t3 is there
+            binding("t0+", 2, 0, 0, 0),
+            binding("u7+ space1", 4, 7, 0, 1),
+            binding("u7", 4, 7, 1, 0),
+            binding("b0", 0, 0, 1, 0),
+            binding("b1", 0, 1, 1, 0),
+            binding("s3", 3, 3, 1, 0),
+            binding("s0", 3, 0, 1, 0),
+        ];
+        let names = |stage| {
+            unbound(&registers, stage, &bindings)
+                .iter()
+                .map(|binding| binding.name.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names("ps"), ["t3-t4", "t0+", "u7", "b1", "s0"]);
+        // the table is only visible to ps, the root constants only to vs
+        assert_eq!(
+            names("vs"),
+            ["t3", "t3-t4", "t0+", "u7+ space1", "u7", "s0"]
+        );
+    }
+}
@@ -0,0 +1,214 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A rough overview of the top-level declarations in preprocessed HLSL.
+//!
+//! This is a heuristic scan of the tokens at brace depth 0, not a parser: it doesn't know the
+//! grammar, so unusual code (macros that survived preprocessing, declarations split in odd ways)
+//! can be missed or misclassified. Use reflection for anything that has to be exact.
+
+#[derive(Default)]
+pub struct Summary {
+    pub functions: Vec<String>,
+    pub cbuffers: Vec<String>,
+    pub textures: Vec<String>,
+    pub samplers: Vec<String>,
+}
+
+/// Splits the source into identifiers (including numbers) and single punctuation characters,
+/// skipping whitespace, comments, string literals and `#` directives.
+fn tokenize(source: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    let mut line_start = true;
+    while let Some(c) = rest.chars().next() {
+        let len = if c == '\n' {
+            line_start = true;
+            1
+        } else if c.is_whitespace() {
+            c.len_utf8()
+        } else if (c == '#' && line_start) || rest.starts_with("//") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            comment.find("*/").map_or(rest.len(), |end| end + 4)
+        } else if c == '"' {
+            rest[1..].find('"').map_or(rest.len(), |end| end + 2)
+        } else {
+            let len = if c.is_alphanumeric() || c == '_' {
+                rest.find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len())
+            } else {
+                c.len_utf8()
+            };
+            tokens.push(&rest[..len]);
+            len
+        };
+        if !c.is_whitespace() {
+            line_start = false;
+        }
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+fn is_identifier(token: &str) -> bool {
+    token
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+}
+
+/// The names declared by a `Type name, name2 : register(x);` statement.
+fn declared_names<'a>(statement: &[&'a str]) -> Vec<&'a str> {
+    let mut names = Vec::new();
+    let mut angle_depth = 0;
+    let mut paren_depth = 0;
+    let mut expect_name = false;
+    for (i, token) in statement.iter().enumerate() {
+        match *token {
+            "<" => angle_depth += 1,
+            ">" => angle_depth -= 1,
+            "(" => paren_depth += 1,
+            ")" => paren_depth -= 1,
+            "," if angle_depth == 0 && paren_depth == 0 => expect_name = true,
+            ":" | "=" if angle_depth == 0 && paren_depth == 0 => expect_name = false,
+            token
+                if i > 0
+                    && angle_depth == 0
+                    && paren_depth == 0
+                    && is_identifier(token)
+                    && (names.is_empty() || expect_name) =>
+            {
+                names.push(token);
+                expect_name = false;
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// The position of the first `(` outside of `[attributes]`.
+fn parameter_list(statement: &[&str]) -> Option<usize> {
+    let mut bracket_depth = 0;
+    statement.iter().position(|token| {
+        match *token {
+            "[" => bracket_depth += 1,
+            "]" => bracket_depth -= 1,
+            "(" => return bracket_depth == 0,
+            _ => {}
+        }
+        false
+    })
+}
+
+/// Scans preprocessed source for its top-level functions, constant buffers, textures and samplers.
+pub fn scan(source: &str) -> Summary {
+    const MODIFIERS: &[&str] = &[
+        "static",
+        "uniform",
+        "const",
+        "extern",
+        "shared",
+        "groupshared",
+        "volatile",
+        "globallycoherent",
+        "precise",
+    ];
+
+    let mut summary = Summary::default();
+    let mut depth = 0usize;
+    let mut statement = Vec::new();
+    for token in tokenize(source) {
+        match token {
+            "{" => {
+                if depth == 0 {
+                    if let ["cbuffer" | "tbuffer", name, ..] = statement[..] {
+                        summary.cbuffers.push(name.to_owned());
+                    } else if let Some(open) = parameter_list(&statement) {
+                        if open > 0 && !statement.contains(&"struct") {
+                            summary.functions.push(statement[open - 1].to_owned());
+                        }
+                    }
+                    statement.clear();
+                }
+                depth += 1;
+            }
+            "}" => {
+                depth = depth.saturating_sub(1);
+                statement.clear();
+            }
+            ";" if depth == 0 => {
+                let start = statement
+                    .iter()
+                    .position(|token| !MODIFIERS.contains(token))
+                    .unwrap_or(statement.len());
+                let declaration = &statement[start..];
+                let names = match declaration.first() {
+                    Some(ty) if ty.starts_with("Texture") || ty.starts_with("RWTexture") => {
+                        &mut summary.textures
+                    }
+                    Some(ty) if ty.starts_with("Sampler") || ty.starts_with("sampler") => {
+                        &mut summary.samplers
+                    }
+                    _ => {
+                        statement.clear();
+                        continue;
+                    }
+                };
+                names.extend(declared_names(declaration).into_iter().map(str::to_owned));
+                statement.clear();
+            }
+            token if depth == 0 => statement.push(token),
+            _ => {}
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_level_declarations() {
+        let source = r#"
+#line 1 "shader.hlsl"
+cbuffer PerFrame : register(b0)
+{
+    float4x4 viewProj;
+};
+tbuffer Lights { float4 colors[4]; }
+Texture2D<float4> albedo : register(t0), normals : register(t1);
+static const Texture2DArray layers;
+RWTexture3D<uint> volume;
+SamplerState linearSampler : register(s0);
+sampler pointSampler;
+struct VSOut { float4 pos : SV_Position; };
+float helper(float x) { return x * 2; }
+/* float commented(float x) { return x; } */
+// Texture2D commentedOut;
+[numthreads(8, 8, 1)]
+void CSMain(uint3 id : SV_DispatchThreadID)
+{
+    if (id.x > 0) { volume[id] = 0; }
+}
+"#;
+        let summary = scan(source);
+        assert_eq!(summary.functions, ["helper", "CSMain"]);
+        assert_eq!(summary.cbuffers, ["PerFrame", "Lights"]);
+        assert_eq!(summary.textures, ["albedo", "normals", "layers", "volume"]);
+        assert_eq!(summary.samplers, ["linearSampler", "pointSampler"]);
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            tokenize("a_1 += \"x y\"; // c\n#define X\n  b/*c*/(2)"),
+            ["a_1", "+", "=", ";", "b", "(", "2", ")"]
+        );
+        // a `#` only starts a directive at the start of a line
+        assert_eq!(tokenize("a # b"), ["a", "#", "b"]);
+    }
+}
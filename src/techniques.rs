@@ -0,0 +1,52 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The `--techniques` file: the entry points and profiles to compile from one source.
+//!
+//! Every line holds a technique as an entry point and a profile separated by whitespace, e.g.
+//! `VSMain vs_5_0`. Blank lines and lines starting with `#` or `//` are ignored.
+
+use std::path::Path;
+
+pub struct Technique {
+    pub entry_point: String,
+    pub profile: String,
+}
+
+/// Parses a techniques file. Returns a description of the first malformed line.
+pub fn parse(text: &str) -> Result<Vec<Technique>, String> {
+    let mut techniques = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        match (fields.next(), fields.next(), fields.next()) {
+            (Some(entry_point), Some(profile), None) => techniques.push(Technique {
+                entry_point: entry_point.to_owned(),
+                profile: profile.to_owned(),
+            }),
+            _ => {
+                return Err(format!(
+                    "line {}: expected `<entry point> <profile>`, got `{line}`",
+                    i + 1
+                ))
+            }
+        }
+    }
+    Ok(techniques)
+}
+
+/// Names an output after the technique's entry point: `out/shader.h` becomes
+/// `out/shader.VSMain.h`.
+pub fn output_name(file: &str, entry_point: &str) -> String {
+    let path = Path::new(file);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}.{entry_point}.{}", extension.to_string_lossy()),
+        None => format!("{stem}.{entry_point}"),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
@@ -0,0 +1,74 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! `--profile-timings`: the time fxc2 spends in each phase, summed over every compile of the
+//! invocation, so a batch reports its totals.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Copy)]
+pub enum Phase {
+    /// Reading the input file
+    Read,
+    /// D3DPreprocess, for /P and --summary
+    Preprocess,
+    /// D3DCompile2
+    Compile,
+    /// Removing parts of the compiled shader
+    Strip,
+    /// Writing the outputs
+    Write,
+}
+
+const PHASE_NAMES: [&str; 5] = ["read", "preprocess", "compile", "strip", "write"];
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TOTALS: Mutex<[Duration; 5]> = Mutex::new([Duration::ZERO; 5]);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Adds the time from its start until it's dropped to its phase, if timing is enabled.
+pub struct Timer {
+    phase: Phase,
+    started: Instant,
+}
+
+pub fn start(phase: Phase) -> Timer {
+    Timer {
+        phase,
+        started: Instant::now(),
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        if enabled() {
+            let mut totals = TOTALS.lock().unwrap_or_else(|err| err.into_inner());
+            totals[self.phase as usize] += self.started.elapsed();
+        }
+    }
+}
+
+/// Prints the time spent in every phase so far as a table, on stderr.
+pub fn print_table() {
+    let totals = *TOTALS.lock().unwrap_or_else(|err| err.into_inner());
+    eprintln!("{:<12} {:>12}", "phase", "time (ms)");
+    for (name, total) in PHASE_NAMES.iter().zip(totals.iter()) {
+        eprintln!("{name:<12} {:>12.3}", total.as_secs_f64() * 1000.0);
+    }
+    let sum = totals.iter().sum::<Duration>();
+    eprintln!("{:<12} {:>12.3}", "total", sum.as_secs_f64() * 1000.0);
+}
@@ -0,0 +1,42 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! `--trace`: logs every compiler API call, its arguments and the HRESULT it returned to stderr.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use windows::Win32::Foundation::S_OK;
+
+use crate::hresult;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Logs a call, if tracing is enabled.
+pub fn call<T>(function: &str, arguments: fmt::Arguments, result: &windows::core::Result<T>) {
+    if !enabled() {
+        return;
+    }
+    let code = match result {
+        Ok(_) => S_OK,
+        Err(err) => err.code(),
+    };
+    match hresult::name(code) {
+        Some(name) => eprintln!(
+            "trace: {function}({arguments}) -> {name} (0x{:08X})",
+            code.0
+        ),
+        None => eprintln!("trace: {function}({arguments}) -> 0x{:08X}", code.0),
+    }
+}